@@ -0,0 +1,93 @@
+//! Run with `cargo bench`. Uses a generated map so it doesn't need a copy of 'G'.
+use alpathfinder::*;
+use std::hint::black_box;
+use std::time::Instant;
+
+const MAP: &str = "bench";
+const SIZE: i32 = 4000;
+const ROOM: i32 = 200;
+
+// A SIZE x SIZE map split into ROOM x ROOM rooms, with a gap in every wall so they're all connected
+fn generate_g() -> String {
+    let mut x_lines = Vec::new();
+    let mut y_lines = Vec::new();
+    for i in (0..=SIZE).step_by(ROOM as usize) {
+        for j in (0..SIZE).step_by(ROOM as usize) {
+            x_lines.push(format!("[{},{},{}]", i, j, j + ROOM / 2));
+            y_lines.push(format!("[{},{},{}]", i, j, j + ROOM / 2));
+        }
+    }
+
+    format!(
+        r#"{{"geometry":{{"{map}":{{"min_x":0,"max_x":{size},"min_y":0,"max_y":{size},"x_lines":[{x}],"y_lines":[{y}]}}}},"maps":{{"{map}":{{"name":"{map}","spawns":[[{spawn},{spawn}]]}}}}}}"#,
+        map = MAP,
+        size = SIZE,
+        x = x_lines.join(","),
+        y = y_lines.join(","),
+        spawn = ROOM / 2,
+    )
+}
+
+fn bench(name: &str, iterations: u32, mut f: impl FnMut()) {
+    // Warm up
+    f();
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:<40} {:>10.3}ms/iter ({} iterations)",
+        name,
+        elapsed.as_secs_f64() * 1000.0 / iterations as f64,
+        iterations
+    );
+}
+
+fn main() {
+    let g = generate_g();
+
+    bench("prepare (4000x4000, 400 rooms)", 10, || {
+        prepare_from_json(black_box(&g), None).unwrap()
+    });
+
+    bench("1000 is_walkable", 1000, || {
+        for i in 0..1000 {
+            black_box(is_walkable(MAP, (i * 37) % SIZE, (i * 91) % SIZE).unwrap());
+        }
+    });
+
+    bench("1000 same_region", 1000, || {
+        for i in 0..1000 {
+            black_box(
+                same_region(MAP, ROOM / 2, ROOM / 2, (i * 37) % SIZE, (i * 91) % SIZE).unwrap(),
+            );
+        }
+    });
+
+    bench("1000 can_walk_path", 100, || {
+        for i in 0..1000 {
+            black_box(
+                can_walk_path(MAP, ROOM / 2, ROOM / 2, (i * 37) % SIZE, (i * 91) % SIZE).unwrap(),
+            );
+        }
+    });
+
+    // The grid search is meant for getting around nearby, so keep the goals within a few rooms
+    bench("100 find_path (within 3x3 rooms)", 10, || {
+        for i in 0..100 {
+            black_box(
+                shortest_path(
+                    MAP,
+                    (ROOM / 2) as f32,
+                    (ROOM / 2) as f32,
+                    ((i * 37) % (ROOM * 3)) as f32,
+                    ((i * 91) % (ROOM * 3)) as f32,
+                    &FindPathOptions::default(),
+                )
+                .unwrap(),
+            );
+        }
+    });
+}
@@ -0,0 +1,84 @@
+//! Prepares every map ahead of time and saves them as a world for `load_world`.
+//!
+//! `cargo run --release --bin precompute -- G.json world.bin > report.json`
+//!
+//! With a directory as the last argument, it also writes each map's walkable areas there as
+//! `<map>.geojson`.
+use alpathfinder::g::GData;
+use alpathfinder::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::{env, fs, process};
+
+#[derive(Serialize)]
+struct Report {
+    g_version: u64,
+    world_bytes: usize,
+    map_hashes: BTreeMap<String, String>,
+    memory: MemoryStats,
+    parse_warnings: Vec<ParseWarning>,
+    validation_issues: Vec<ValidationIssue>,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 && args.len() != 4 {
+        eprintln!(
+            "Usage: {} <G.json> <world output> [GeoJSON output directory]",
+            args[0]
+        );
+        process::exit(1);
+    }
+
+    let g_json = fs::read_to_string(&args[1]).unwrap_or_else(|e| {
+        eprintln!("Couldn't read {}: {}", args[1], e);
+        process::exit(1);
+    });
+    let g: GData = serde_json::from_str(&g_json).unwrap_or_else(|e| {
+        eprintln!("Couldn't parse {}: {}", args[1], e);
+        process::exit(1);
+    });
+
+    let validation_issues = validate(&g);
+    prepare_from_json(&g_json, None).unwrap_or_else(|e| {
+        eprintln!("Couldn't prepare {}: {}", args[1], e);
+        process::exit(1);
+    });
+
+    let world = export_world();
+    fs::write(&args[2], &world).unwrap_or_else(|e| {
+        eprintln!("Couldn't write {}: {}", args[2], e);
+        process::exit(1);
+    });
+
+    if let Some(dir) = args.get(3) {
+        fs::create_dir_all(dir).unwrap_or_else(|e| {
+            eprintln!("Couldn't create {}: {}", dir, e);
+            process::exit(1);
+        });
+        for map_name in get_map_list() {
+            let path = format!("{}/{}.geojson", dir, map_name);
+            let geojson = serde_json::to_string(&walkable_geojson(&map_name).unwrap()).unwrap();
+            fs::write(&path, geojson).unwrap_or_else(|e| {
+                eprintln!("Couldn't write {}: {}", path, e);
+                process::exit(1);
+            });
+        }
+    }
+
+    let report = Report {
+        g_version: g.version,
+        world_bytes: world.len(),
+        map_hashes: get_map_list()
+            .into_iter()
+            .map(|m| {
+                let hash = get_map_hash(&m).unwrap();
+                (m, hash)
+            })
+            .collect(),
+        memory: memory_stats(),
+        parse_warnings: parse_warnings(),
+        validation_issues,
+    };
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+}
@@ -1,5 +1,157 @@
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
 use std::collections::HashMap;
+use std::ops::Deref;
+
+#[derive(Debug)]
+pub struct GDoor {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub map: String,
+    pub spawn: usize,
+    pub from_spawn: Option<usize>,
+    // Optional trailing type (e.g. stairs that transition on contact instead of on click)
+    pub kind: Option<String>,
+}
+
+// Entries of G that are stored as loosely typed arrays (doors, spawns, ...)
+pub trait FromEntry: Sized {
+    fn from_entry(v: &[Value]) -> Result<Self, String>;
+}
+
+impl FromEntry for GDoor {
+    fn from_entry(v: &[Value]) -> Result<Self, String> {
+        let number = |i: usize| {
+            v.get(i)
+                .and_then(Value::as_f64)
+                .map(|n| n as f32)
+                .ok_or(format!("element {} is not a number", i))
+        };
+        let index = |i: usize| v.get(i).and_then(Value::as_u64).map(|n| n as usize);
+
+        Ok(GDoor {
+            x: number(0)?,
+            y: number(1)?,
+            width: number(2)?,
+            height: number(3)?,
+            map: v
+                .get(4)
+                .and_then(Value::as_str)
+                .ok_or("element 4 is not a map name")?
+                .to_string(),
+            spawn: index(5).ok_or("element 5 is not a spawn index")?,
+            from_spawn: index(6),
+            kind: v.get(7).and_then(Value::as_str).map(str::to_string),
+        })
+    }
+}
+
+impl FromEntry for Vec<f32> {
+    fn from_entry(v: &[Value]) -> Result<Self, String> {
+        if v.len() < 2 {
+            return Err("expected at least an x and a y".to_string());
+        }
+        v.iter()
+            .enumerate()
+            .map(|(i, n)| {
+                n.as_f64()
+                    .map(|n| n as f32)
+                    .ok_or(format!("element {} is not a number", i))
+            })
+            .collect()
+    }
+}
+
+// The entries that parsed, plus the index and reason for every entry that didn't, so bad
+// data can be reported instead of silently disappearing
+#[derive(Debug)]
+pub struct Lenient<T> {
+    pub entries: Vec<T>,
+    pub skipped: Vec<(usize, String)>,
+}
+
+impl<T> Default for Lenient<T> {
+    fn default() -> Self {
+        Lenient {
+            entries: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+}
+
+impl<T> Lenient<T> {
+    // Gets an entry by its index in the original G array, which is different from its index in
+    // `entries` if an earlier entry was skipped
+    pub fn by_index(&self, index: usize) -> Option<&T> {
+        let mut skipped_before = 0;
+        for (i, _) in &self.skipped {
+            if *i == index {
+                return None;
+            } else if *i < index {
+                skipped_before += 1;
+            }
+        }
+        self.entries.get(index - skipped_before)
+    }
+}
+
+impl<T> Deref for Lenient<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entries
+    }
+}
+
+impl<'de, T: FromEntry> Deserialize<'de> for Lenient<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw: Option<Vec<Value>> = Deserialize::deserialize(deserializer)?;
+        let mut lenient = Lenient::default();
+        for (i, entry) in raw.into_iter().flatten().enumerate() {
+            let parsed = match &entry {
+                Value::Array(v) => T::from_entry(v),
+                _ => Err("not an array".to_string()),
+            };
+            match parsed {
+                Ok(v) => lenient.entries.push(v),
+                Err(e) => lenient.skipped.push((i, format!("{} ({})", e, entry))),
+            }
+        }
+        Ok(lenient)
+    }
+}
+
+// A wall, [x, y1, y2] in `x_lines` (vertical) or [y, x1, x2] in `y_lines` (horizontal)
+#[derive(Debug)]
+pub struct GLine {
+    pub at: i32,
+    pub from: i32,
+    pub to: i32,
+}
+
+impl FromEntry for GLine {
+    fn from_entry(v: &[Value]) -> Result<Self, String> {
+        if v.len() != 3 {
+            return Err(format!("expected 3 elements, got {}", v.len()));
+        }
+        let coordinate = |i: usize| {
+            v[i].as_i64()
+                .and_then(|n| i32::try_from(n).ok())
+                .ok_or(format!("element {} is not an integer", i))
+        };
+
+        Ok(GLine {
+            at: coordinate(0)?,
+            from: coordinate(1)?,
+            to: coordinate(2)?,
+        })
+    }
+}
 
 #[derive(Deserialize, Debug)]
 pub struct GGeometry {
@@ -7,21 +159,217 @@ pub struct GGeometry {
     pub max_x: i32,
     pub min_y: i32,
     pub max_y: i32,
-    pub x_lines: Option<Vec<Vec<i32>>>,
-    pub y_lines: Option<Vec<Vec<i32>>>,
+    #[serde(default)]
+    pub x_lines: Lenient<GLine>,
+    #[serde(default)]
+    pub y_lines: Lenient<GLine>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct GMap {
+    #[serde(default)]
+    pub doors: Lenient<GDoor>,
     pub ignore: Option<bool>,
+    // Instanced maps (e.g. dungeons) that you need to enter
+    pub instance: Option<bool>,
+    pub irregular: Option<bool>,
     pub name: String,
+    // Maps you can walk off the edge of
+    pub no_bounds: Option<bool>,
+    #[serde(default)]
+    pub monsters: Vec<GMapMonster>,
+    #[serde(default)]
+    pub npcs: Vec<GMapNpc>,
     pub pvp: Option<bool>,
-    pub spawns: Vec<Vec<f32>>,
+    pub spawns: Lenient<Vec<f32>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GMapMonster {
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    // Where they spawn on this map (x1, y1, x2, y2)
+    pub boundary: Option<Vec<f32>>,
+    // Monsters that roam between maps spawn in each of these instead
+    #[serde(default)]
+    pub boundaries: Lenient<GBoundary>,
+}
+
+// A rectangle on a map, [map, x1, y1, x2, y2] in 'G'
+#[derive(Debug)]
+pub struct GBoundary {
+    pub map: String,
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+}
+
+impl FromEntry for GBoundary {
+    fn from_entry(v: &[Value]) -> Result<Self, String> {
+        let number = |i: usize| {
+            v.get(i)
+                .and_then(Value::as_f64)
+                .map(|n| n as f32)
+                .ok_or(format!("element {} is not a number", i))
+        };
+
+        Ok(GBoundary {
+            map: v
+                .first()
+                .and_then(Value::as_str)
+                .ok_or("element 0 is not a map name")?
+                .to_string(),
+            x1: number(1)?,
+            y1: number(2)?,
+            x2: number(3)?,
+            y2: number(4)?,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GMapNpc {
+    // Roaming NPCs walk around in this rectangle (x1, y1, x2, y2) instead of standing still
+    pub boundary: Option<Vec<f32>>,
+    pub id: String,
+    pub name: Option<String>,
+    pub position: Option<Vec<f32>>,
+    pub positions: Option<Vec<Vec<f32>>>,
+}
+
+impl GMapNpc {
+    // Where to find the NPC on the map. Roaming NPCs are placed in the middle of their boundary.
+    pub fn locations(&self) -> Vec<(f32, f32)> {
+        let mut locations = Vec::new();
+        if let Some(p) = &self.position {
+            locations.push(p);
+        }
+        if let Some(positions) = &self.positions {
+            locations.extend(positions);
+        }
+        let mut locations: Vec<(f32, f32)> = locations
+            .into_iter()
+            .filter(|p| p.len() >= 2)
+            .map(|p| (p[0], p[1]))
+            .collect();
+
+        if let Some(b) = &self.boundary {
+            if b.len() >= 4 {
+                locations.push(((b[0] + b[2]) / 2.0, (b[1] + b[3]) / 2.0));
+            }
+        }
+        locations
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GNpc {
+    pub name: Option<String>,
+    // Maps (and the spawn on them) the NPC can teleport you to, e.g. for the transporter
+    pub places: Option<HashMap<String, usize>>,
+    pub role: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct GData {
     pub geometry: HashMap<String, GGeometry>,
     pub maps: HashMap<String, GMap>,
+    #[serde(default)]
+    pub npcs: HashMap<String, GNpc>,
+    #[serde(default)]
     pub version: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse<T: FromEntry>(json: &str) -> Lenient<T> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn entries_are_found_by_their_index_in_g() {
+        // Entries 1 and 4 are skipped
+        let spawns: Lenient<Vec<f32>> = parse("[[0,0],[1],[2,2],[3,3],\"4\",[5,5]]");
+        assert_eq!(spawns.len(), 4);
+        assert_eq!(
+            spawns.skipped.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            [1, 4]
+        );
+
+        assert_eq!(spawns.by_index(0), Some(&vec![0.0, 0.0]));
+        assert_eq!(spawns.by_index(1), None);
+        assert_eq!(spawns.by_index(2), Some(&vec![2.0, 2.0]));
+        assert_eq!(spawns.by_index(3), Some(&vec![3.0, 3.0]));
+        assert_eq!(spawns.by_index(4), None);
+        assert_eq!(spawns.by_index(5), Some(&vec![5.0, 5.0]));
+        assert_eq!(spawns.by_index(6), None);
+
+        let missing: Lenient<Vec<f32>> = parse("null");
+        assert!(missing.is_empty() && missing.skipped.is_empty());
+    }
+
+    #[test]
+    fn doors_are_parsed() {
+        let doors: Lenient<GDoor> = parse(
+            r#"[[10,20,30,40,"main",1],[10,20,30,40,"cave",2,0,"ladder"],[10,20,30,"main",1],[10,20,30,40,"main"]]"#,
+        );
+        assert_eq!(doors.len(), 2);
+
+        let door = doors.by_index(0).unwrap();
+        assert_eq!(
+            (door.x, door.y, door.width, door.height),
+            (10.0, 20.0, 30.0, 40.0)
+        );
+        assert_eq!((door.map.as_str(), door.spawn), ("main", 1));
+        assert_eq!((door.from_spawn, door.kind.as_deref()), (None, None));
+
+        let door = doors.by_index(1).unwrap();
+        assert_eq!((door.map.as_str(), door.spawn), ("cave", 2));
+        assert_eq!(
+            (door.from_spawn, door.kind.as_deref()),
+            (Some(0), Some("ladder"))
+        );
+
+        // A missing height, and a missing spawn
+        assert_eq!(doors.skipped[0].0, 2);
+        assert!(doors.skipped[0].1.starts_with("element 3 is not a number"));
+        assert_eq!(doors.skipped[1].0, 3);
+        assert!(doors.skipped[1]
+            .1
+            .starts_with("element 5 is not a spawn index"));
+    }
+
+    #[test]
+    fn lines_are_parsed() {
+        let lines: Lenient<GLine> =
+            parse("[[10,-20,30],[10,20],[10,20,30,40],[10.5,20,30],[10,20,3000000000]]");
+        assert_eq!(lines.len(), 1);
+        let line = &lines[0];
+        assert_eq!((line.at, line.from, line.to), (10, -20, 30));
+
+        let reasons: Vec<&str> = lines.skipped.iter().map(|(_, r)| r.as_str()).collect();
+        assert!(reasons[0].starts_with("expected 3 elements, got 2"));
+        assert!(reasons[1].starts_with("expected 3 elements, got 4"));
+        assert!(reasons[2].starts_with("element 0 is not an integer"));
+        assert!(reasons[3].starts_with("element 2 is not an integer"));
+    }
+
+    #[test]
+    fn boundaries_are_parsed() {
+        let boundaries: Lenient<GBoundary> =
+            parse(r#"[["main",-10,-20,30,40],[-10,-20,30,40],["main",-10,-20,30]]"#);
+        assert_eq!(boundaries.len(), 1);
+        let b = &boundaries[0];
+        assert_eq!(
+            (b.map.as_str(), b.x1, b.y1, b.x2, b.y2),
+            ("main", -10.0, -20.0, 30.0, 40.0)
+        );
+
+        let reasons: Vec<&str> = boundaries.skipped.iter().map(|(_, r)| r.as_str()).collect();
+        assert!(reasons[0].starts_with("element 0 is not a map name"));
+        assert!(reasons[1].starts_with("element 4 is not a number"));
+    }
+}
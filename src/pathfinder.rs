@@ -0,0 +1,1278 @@
+use core::cmp::{max, min};
+use lazy_static::lazy_static;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+use wasm_bindgen::prelude::*;
+
+mod locations;
+mod monsters;
+mod overrides;
+mod paths;
+mod polygons;
+#[cfg(feature = "prepare")]
+mod prepare;
+mod search;
+#[cfg(all(test, feature = "prepare"))]
+mod testing;
+#[cfg(feature = "prepare")]
+mod validate;
+mod walls;
+mod world;
+pub use locations::{add_location, find_path_to_location, get_location, location_on_map};
+pub use monsters::{find_path_to_monster, path_to_monster};
+pub use overrides::{add_overrides, apply_overrides, MapOverrides};
+pub use paths::{
+    first_invalid_segment, path_length, path_steps, simplified_path, simplify_path, split_path,
+    validate_path, PathStep,
+};
+pub use polygons::{
+    get_walkable_geojson, get_walkable_polygons, walkable_geojson, walkable_polygons,
+    GeoJsonFeatureCollection, WalkablePolygon,
+};
+#[cfg(feature = "prepare")]
+pub use prepare::*;
+pub use search::{
+    derive_follow_path, find_path, find_path_f32, find_path_into, find_path_to_polygon,
+    find_path_to_rect, find_paths, follow_path, get_distance, shortest_path, shortest_path_to_area,
+    shortest_paths, FindPathOptions, GoalArea, PathPoint, PathQuery,
+};
+#[cfg(feature = "prepare")]
+pub use validate::{validate, ValidationIssue};
+pub use walls::{closest_wall, get_walls, has_line_of_sight, nearest_wall, NearestWall};
+pub use world::{export_world, load_world};
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "(message: string) => void")]
+    pub type LogSink;
+
+    #[wasm_bindgen(method, js_name = call)]
+    fn call(this: &LogSink, this_arg: &JsValue, message: &str);
+}
+
+// `catch`, so a missing or shadowed `console` (in some worker sandboxes) doesn't throw
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console, js_name = log, catch)]
+    fn console_log(s: &str) -> Result<(), JsValue>;
+}
+
+thread_local! {
+    static LOG_SINK: std::cell::RefCell<Option<LogSink>> = const { std::cell::RefCell::new(None) };
+}
+
+#[cfg(target_arch = "wasm32")]
+#[cfg_attr(not(feature = "prepare"), allow(dead_code))]
+fn log(s: &str) {
+    LOG_SINK.with(|sink| match &*sink.borrow() {
+        Some(sink) => sink.call(&JsValue::NULL, s),
+        None => {
+            let _ = console_log(s);
+        }
+    });
+}
+
+// There's no `console` outside of wasm (e.g. when benchmarking natively)
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg_attr(not(feature = "prepare"), allow(dead_code))]
+fn log(s: &str) {
+    eprintln!("{}", s);
+}
+
+/// Sends the messages the pathfinder logs (e.g. how long preparing took) to `sink` instead of
+/// `console.log`. Pass `undefined` to go back to the console.
+#[wasm_bindgen]
+pub fn set_log_sink(sink: Option<LogSink>) {
+    LOG_SINK.with(|current| *current.borrow_mut() = sink);
+}
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = Math)]
+    fn random() -> f64;
+
+    #[wasm_bindgen(js_namespace = Reflect, js_name = set)]
+    fn reflect_set(target: &JsValue, key: &JsValue, value: &JsValue) -> bool;
+}
+
+// What a tile's cells are, so queries can skip over tiles that are all the same
+#[derive(Clone, Copy, PartialEq)]
+enum Tile {
+    Walkable,
+    Blocked,
+    Mixed,
+}
+
+struct Grid {
+    width: i32,
+    min_x: i32,
+    min_y: i32,
+    data: Vec<u8>,
+    // A summary of each TILE_SIZE x TILE_SIZE tile (row by row). Filled in by `summarize` once
+    // the grid is done.
+    tiles: Vec<Tile>,
+}
+
+impl Grid {
+    fn height(&self) -> i32 {
+        self.data.len() as i32 / self.width
+    }
+
+    fn tiles_width(&self) -> i32 {
+        (self.width + TILE_SIZE - 1) / TILE_SIZE
+    }
+
+    fn summarize(&mut self) {
+        let tiles_width = self.tiles_width();
+        let tiles_height = (self.height() + TILE_SIZE - 1) / TILE_SIZE;
+        let mut any_walkable = vec![false; (tiles_width * tiles_height) as usize];
+        let mut any_blocked = vec![false; (tiles_width * tiles_height) as usize];
+        for (y, row) in self.data.chunks(self.width as usize).enumerate() {
+            let tiles_row = y as i32 / TILE_SIZE * tiles_width;
+            for (x, cells) in row.chunks(TILE_SIZE as usize).enumerate() {
+                let tile = (tiles_row + x as i32) as usize;
+                any_walkable[tile] |= cells.iter().any(|cell| *cell >= WALKABLE);
+                any_blocked[tile] |= cells.iter().any(|cell| *cell < WALKABLE);
+            }
+        }
+        self.tiles = any_walkable
+            .iter()
+            .zip(&any_blocked)
+            .map(|(walkable, blocked)| match (walkable, blocked) {
+                (true, false) => Tile::Walkable,
+                (false, true) => Tile::Blocked,
+                _ => Tile::Mixed,
+            })
+            .collect();
+    }
+
+    // The tile the game coordinates are in
+    fn tile_of(&self, x: i32, y: i32) -> (i32, i32) {
+        let tile = |v: i32, min: i32| (v as i64 - min as i64).div_euclid(TILE_SIZE as i64) as i32;
+        (tile(x, self.min_x), tile(y, self.min_y))
+    }
+
+    // Tiles outside of the grid are blocked, like the cells in them
+    fn tile(&self, (tx, ty): (i32, i32)) -> Tile {
+        if tx < 0 || ty < 0 || tx >= self.tiles_width() {
+            return Tile::Blocked;
+        }
+        self.tiles
+            .get((ty * self.tiles_width() + tx) as usize)
+            .copied()
+            .unwrap_or(Tile::Blocked)
+    }
+
+    // Whether every tile touching the rectangle (game coordinates, inclusive) is `state`. Only
+    // checks the tiles if there are at most `max_tiles` of them.
+    fn all_tiles(&self, x1: i32, y1: i32, x2: i32, y2: i32, state: Tile, max_tiles: i32) -> bool {
+        let (tx1, ty1) = self.tile_of(x1, y1);
+        let (tx2, ty2) = self.tile_of(x2, y2);
+        if (tx2 as i64 - tx1 as i64 + 1) * (ty2 as i64 - ty1 as i64 + 1) > max_tiles as i64 {
+            return false;
+        }
+        (ty1..=ty2).all(|ty| (tx1..=tx2).all(|tx| self.tile((tx, ty)) == state))
+    }
+
+    // The index in `data` of the cell at the game coordinates, if it's in the grid. Works in
+    // i64, so coordinates far outside of the map can't overflow.
+    fn index(&self, x_i: i32, y_i: i32) -> Option<usize> {
+        let x = x_i as i64 - self.min_x as i64;
+        let y = y_i as i64 - self.min_y as i64;
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height() as i64 {
+            return None;
+        }
+        usize::try_from(y * self.width as i64 + x).ok()
+    }
+
+    // Sets the cells in [x1, x2) x [y1, y2) (game coordinates) that are in the grid to `value`.
+    // Clamps in i64, so rects reaching far outside of the map can't overflow.
+    fn fill(&mut self, x1: i64, y1: i64, x2: i64, y2: i64, value: u8) {
+        let (min_x, min_y) = (self.min_x as i64, self.min_y as i64);
+        let x_from = x1.saturating_sub(min_x).max(0);
+        let x_to = x2.saturating_sub(min_x).min(self.width as i64);
+        let y_from = y1.saturating_sub(min_y).max(0);
+        let y_to = y2.saturating_sub(min_y).min(self.height() as i64);
+        for y in y_from..y_to {
+            for x in x_from..x_to {
+                self.data[(y * self.width as i64 + x) as usize] = value;
+            }
+        }
+    }
+
+    // Whether the game coordinates (as they are, e.g. `character.x`) are on the grid
+    fn contains(&self, x: f32, y: f32) -> bool {
+        !x.is_nan() && !y.is_nan() && self.index(to_grid(x), to_grid(y)).is_some()
+    }
+
+    // Takes game coordinates, anything outside of the grid is not walkable
+    fn is_walkable(&self, x_i: i32, y_i: i32) -> bool {
+        self.index(x_i, y_i)
+            .is_some_and(|i| self.data[i] >= WALKABLE)
+    }
+
+    // Walks the cells on the line between the two game coordinates
+    fn can_walk_path(&self, x1: i32, y1: i32, x2: i32, y2: i32) -> bool {
+        // Points in different regions are never connected
+        match (self.region(x1, y1), self.region(x2, y2)) {
+            (Some(a), Some(b)) if regions_connected(a, b) != Some(false) => {}
+            _ => return false,
+        }
+        // Nothing to walk if there's no wall anywhere around the line. Looking at more tiles
+        // than the line has cells would be slower than walking it.
+        let length = max((x2 - x1).abs(), (y2 - y1).abs()) + 1;
+        let (left, top, right, bottom) = (min(x1, x2), min(y1, y2), max(x1, x2), max(y1, y2));
+        if self.all_tiles(left, top, right, bottom, Tile::Walkable, length) {
+            return true;
+        }
+
+        let dx = (x2 - x1).abs();
+        let dy = -(y2 - y1).abs();
+        let sx = if x1 < x2 { 1 } else { -1 };
+        let sy = if y1 < y2 { 1 } else { -1 };
+        let mut error = dx + dy;
+
+        // Cells only have to be looked at in tiles that are part walkable
+        let mut tile_xy = self.tile_of(x1, y1);
+        let mut tile = self.tile(tile_xy);
+        let (mut x, mut y) = (x1, y1);
+        loop {
+            let xy = self.tile_of(x, y);
+            if xy != tile_xy {
+                tile_xy = xy;
+                tile = self.tile(xy);
+            }
+            match tile {
+                Tile::Walkable => {}
+                Tile::Blocked => return false,
+                Tile::Mixed => {
+                    if !self.is_walkable(x, y) {
+                        return false;
+                    }
+                }
+            }
+            if x == x2 && y == y2 {
+                return true;
+            }
+            let e2 = 2 * error;
+            if e2 >= dy {
+                error += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                error += dx;
+                y += sy;
+            }
+        }
+    }
+
+    // The closest walkable cell to the game coordinates that's within `max_distance` of them and
+    // that `accept` agrees with, looking outwards one square ring at a time. Cells it rejects are
+    // passed over for the next closest one.
+    fn nearest_walkable(
+        &self,
+        x: i32,
+        y: i32,
+        max_distance: i32,
+        accept: impl Fn(i32, i32) -> bool,
+    ) -> Option<(i32, i32)> {
+        // Works in i64 and only looks at the parts of rings that are on the grid, so points far
+        // outside of it can't overflow or take forever
+        let (x, y, max_distance) = (x as i64, y as i64, max_distance as i64);
+        let (x1, y1) = (self.min_x as i64, self.min_y as i64);
+        let (x2, y2) = (x1 + self.width as i64 - 1, y1 + self.height() as i64 - 1);
+        // Rings closer than `near` don't reach the grid, rings further than `far` are past it
+        let near = max((x1 - x).max(x - x2), (y1 - y).max(y - y2)).max(0);
+        let far = max((x - x1).max(x2 - x), (y - y1).max(y2 - y));
+
+        let mut best: Option<((i32, i32), i64)> = None;
+        for r in near..=max_distance.min(far) {
+            // Cells on this ring and further out are at least `r` away
+            if best.is_some_and(|(_, d)| r * r >= d) {
+                break;
+            }
+            // The ring's sides as [from, to] (inclusive), cut to the grid
+            let sides = [
+                ((x - r, y - r), (x + r, y - r)),
+                ((x - r, y + r), (x + r, y + r)),
+                ((x - r, y - r), (x - r, y + r)),
+                ((x + r, y - r), (x + r, y + r)),
+            ];
+            for ((x_from, y_from), (x_to, y_to)) in sides {
+                let (x_from, x_to) = (x_from.max(x1), x_to.min(x2));
+                let (y_from, y_to) = (y_from.max(y1), y_to.min(y2));
+                if x_from > x_to || y_from > y_to {
+                    continue;
+                }
+                let (x_from, y_from, x_to, y_to) =
+                    (x_from as i32, y_from as i32, x_to as i32, y_to as i32);
+                // Skip the sides that are all in blocked tiles
+                if self.all_tiles(x_from, y_from, x_to, y_to, Tile::Blocked, i32::MAX) {
+                    continue;
+                }
+                for cy in y_from..=y_to {
+                    for cx in x_from..=x_to {
+                        let (dx, dy) = (cx as i64 - x, cy as i64 - y);
+                        let d = dx * dx + dy * dy;
+                        if d <= max_distance * max_distance
+                            && best.is_none_or(|(_, best_d)| d < best_d)
+                            && self.is_walkable(cx, cy)
+                            && accept(cx, cy)
+                        {
+                            best = Some(((cx, cy), d));
+                        }
+                    }
+                }
+            }
+        }
+        best.map(|(cell, _)| cell)
+    }
+
+    // The id of the walkable region the game coordinates are in, if they are walkable
+    fn region(&self, x_i: i32, y_i: i32) -> Option<u8> {
+        let cell = self.data[self.index(x_i, y_i)?];
+        cell.checked_sub(WALKABLE)
+    }
+
+    // Whether there's a walking path between the game coordinates. Uses the regions when they
+    // tell, and otherwise (both in `SHARED_REGION`) looks for one cell by cell.
+    fn connected(&self, x1: i32, y1: i32, x2: i32, y2: i32) -> bool {
+        let (a, b) = match (self.region(x1, y1), self.region(x2, y2)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return false,
+        };
+        if let Some(connected) = regions_connected(a, b) {
+            return connected;
+        }
+
+        // Flood the shared region from the first point, the same 4 directions `fill_from` does
+        let start = self.index(x1, y1).unwrap();
+        let goal = self.index(x2, y2).unwrap();
+        let width = self.width as usize;
+        let mut seen = vec![false; self.data.len()];
+        seen[start] = true;
+        let mut stack = vec![start];
+        while let Some(i) = stack.pop() {
+            if i == goal {
+                return true;
+            }
+            let (x, y) = (i % width, i / width);
+            let neighbours = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < width).then(|| i + 1),
+                (y > 0).then(|| i - width),
+                (i + width < self.data.len()).then(|| i + width),
+            ];
+            for n in neighbours.into_iter().flatten() {
+                if !seen[n] && self.data[n] == WALKABLE + SHARED_REGION {
+                    seen[n] = true;
+                    stack.push(n);
+                }
+            }
+        }
+        false
+    }
+
+    // Labels the cells that can be reached from the spawns, with a region for each connected
+    // area. Cells that should be walkable have to be `UNKNOWN` beforehand.
+    fn fill_regions(&mut self, spawns: &[Vec<f32>]) {
+        let mut region = WALKABLE;
+        for spawn in spawns {
+            let spawn = Spawn::from_entry(spawn);
+            let (x, y) = (to_grid(spawn.x), to_grid(spawn.y));
+
+            // Spawns outside of the map (see `validate_g`) have nothing to fill
+            if self.index(x, y).is_some_and(|i| self.data[i] < WALKABLE) {
+                self.fill_from(x - self.min_x, y - self.min_y, region);
+                // Maps don't have anywhere near 250 separate areas, but if one does, the last
+                // regions share an id (`SHARED_REGION`)
+                region = region.saturating_add(1);
+            }
+
+            // Spawns with a size cover more ground, which might not be connected to their
+            // middle
+            if let Some((x1, y1, x2, y2)) = spawn.rect() {
+                let x_from = (to_grid(x1) - self.min_x).max(0);
+                let x_to = (to_grid(x2) - self.min_x).min(self.width - 1);
+                let y_from = (to_grid(y1) - self.min_y).max(0);
+                let y_to = (to_grid(y2) - self.min_y).min(self.height() - 1);
+                for y in y_from..=y_to {
+                    for x in x_from..=x_to {
+                        if self.data[(y * self.width + x) as usize] == UNKNOWN {
+                            self.fill_from(x, y, region);
+                            region = region.saturating_add(1);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Scanline flood fill of the `UNKNOWN` cells connected to (x, y) (grid coordinates)
+    fn fill_from(&mut self, x: i32, y: i32, region: u8) {
+        let width = self.width;
+        let height = self.height();
+
+        let mut stack: Vec<(i32, i32)> = Vec::new();
+        stack.push((y, x));
+        while let Some((y, mut x)) = stack.pop() {
+            // log("working");
+            while x >= 0 && self.data[(y * width + x) as usize] == UNKNOWN {
+                x -= 1;
+            }
+            x += 1;
+            let mut span_above = false;
+            let mut span_below = false;
+            while x < width && self.data[(y * width + x) as usize] == UNKNOWN {
+                self.data[(y * width + x) as usize] = region;
+                if !span_above && y > 0 && self.data[((y - 1) * width + x) as usize] == UNKNOWN {
+                    stack.push((y - 1, x));
+                    span_above = true;
+                } else if span_above
+                    && y > 0
+                    && self.data[((y - 1) * width + x) as usize] != UNKNOWN
+                {
+                    span_above = false;
+                }
+
+                if !span_below
+                    && y < height - 1
+                    && self.data[((y + 1) * width + x) as usize] == UNKNOWN
+                {
+                    stack.push((y + 1, x));
+                    span_below = true;
+                } else if span_below
+                    && y < height - 1
+                    && self.data[((y + 1) * width + x) as usize] != UNKNOWN
+                {
+                    span_below = false;
+                }
+                x += 1;
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+// A spawn from 'G', which is [x, y] and sometimes [x, y, width, height, theta]
+#[derive(Serialize)]
+struct Spawn {
+    x: f32,
+    y: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    theta: Option<f32>,
+}
+
+impl Spawn {
+    fn from_entry(spawn: &[f32]) -> Spawn {
+        Spawn {
+            x: spawn[0],
+            y: spawn[1],
+            width: spawn.get(2).copied(),
+            height: spawn.get(3).copied(),
+            theta: spawn.get(4).copied(),
+        }
+    }
+
+    // The area around the spawn, if it has a size. The rotation is left out.
+    fn rect(&self) -> Option<(f32, f32, f32, f32)> {
+        let (width, height) = (self.width?, self.height?);
+        Some((
+            self.x - width / 2.0,
+            self.y - height / 2.0,
+            self.x + width / 2.0,
+            self.y + height / 2.0,
+        ))
+    }
+}
+
+#[derive(Serialize)]
+struct MapInfo {
+    min_x: i32,
+    max_x: i32,
+    min_y: i32,
+    max_y: i32,
+    spawns: Vec<Vec<f32>>,
+    // Indexed like the map's doors in 'G', with `None` for doors that couldn't be parsed
+    #[serde(skip)]
+    doors: Vec<Option<Door>>,
+    #[serde(skip)]
+    transporters: Vec<Transporter>,
+    #[serde(skip)]
+    npcs: Vec<NpcLocation>,
+    // Hash of the parts of 'G' the map was prepared from, to tell when it's out of date
+    #[serde(skip)]
+    hash: u64,
+    // The map's walls as they are in 'G', without the margin the grid keeps around them
+    #[serde(skip)]
+    walls: Vec<Wall>,
+    #[serde(skip)]
+    monsters: Vec<MonsterArea>,
+}
+
+#[derive(Serialize)]
+struct Door {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    map: String,
+    spawn: usize,
+    // The type from 'G', if there is one. Doors with a type (e.g. stairs) take you through as
+    // soon as you walk into them, the others have to be used from close enough.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+}
+
+impl Door {
+    fn on_contact(&self) -> bool {
+        self.kind.is_some()
+    }
+}
+
+// A wall line from 'G' (x_lines are vertical, y_lines horizontal), in game coordinates
+#[derive(Clone, Copy)]
+struct Wall {
+    x1: i32,
+    y1: i32,
+    x2: i32,
+    y2: i32,
+}
+
+// Where a type of monster spawns on the map, in game coordinates
+#[derive(Clone, PartialEq)]
+struct MonsterArea {
+    kind: String,
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+}
+
+#[derive(Serialize, Clone)]
+struct NpcLocation {
+    id: String,
+    name: Option<String>,
+    map: String,
+    x: f32,
+    y: f32,
+}
+
+impl MapInfo {
+    // Rough number of bytes used, including what the vectors point to
+    fn size_of(&self) -> usize {
+        let spawns: usize = self
+            .spawns
+            .iter()
+            .map(|s| std::mem::size_of::<Vec<f32>>() + s.capacity() * 4)
+            .sum();
+        let doors: usize = self
+            .doors
+            .iter()
+            .map(|d| {
+                std::mem::size_of::<Option<Door>>()
+                    + d.as_ref().map_or(0, |d| {
+                        d.map.capacity() + d.kind.as_ref().map_or(0, String::capacity)
+                    })
+            })
+            .sum();
+        let transporters: usize = self
+            .transporters
+            .iter()
+            .map(|t| {
+                std::mem::size_of::<Transporter>()
+                    + t.id.capacity()
+                    + t.destinations
+                        .iter()
+                        .map(|d| std::mem::size_of::<Destination>() + d.map.capacity())
+                        .sum::<usize>()
+            })
+            .sum();
+        let npcs: usize = self
+            .npcs
+            .iter()
+            .map(|n| {
+                std::mem::size_of::<NpcLocation>()
+                    + n.id.capacity()
+                    + n.map.capacity()
+                    + n.name.as_ref().map_or(0, String::capacity)
+            })
+            .sum();
+        let walls = self.walls.capacity() * std::mem::size_of::<Wall>();
+        let monsters: usize = self
+            .monsters
+            .iter()
+            .map(|m| std::mem::size_of::<MonsterArea>() + m.kind.capacity())
+            .sum();
+        std::mem::size_of::<MapInfo>() + spawns + doors + transporters + npcs + walls + monsters
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct Destination {
+    map: String,
+    spawn: usize,
+}
+
+// An NPC that can teleport you to other maps
+#[derive(Serialize, Clone)]
+struct Transporter {
+    id: String,
+    x: f32,
+    y: f32,
+    destinations: Vec<Destination>,
+}
+
+lazy_static! {
+    // Prepared maps are never modified, only replaced, so queries can hold on to an `Arc` of
+    // them without keeping the lock
+    static ref GRIDS: RwLock<HashMap<String, Arc<Grid>>> = {
+        let m = HashMap::new();
+        RwLock::new(m)
+    };
+    static ref MAPS: RwLock<HashMap<String, Arc<MapInfo>>> = RwLock::new(HashMap::new());
+    static ref CONSTANTS: Mutex<Constants> = Mutex::new(Constants::default());
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &'static str = r#"
+export interface MapBounds {
+    min_x: number;
+    max_x: number;
+    min_y: number;
+    max_y: number;
+    spawns: number[][];
+}
+
+export interface Point {
+    x: number;
+    y: number;
+}
+
+export interface Spawn {
+    x: number;
+    y: number;
+    width?: number;
+    height?: number;
+    theta?: number;
+}
+
+export interface Door {
+    x: number;
+    y: number;
+    width: number;
+    height: number;
+    map: string;
+    spawn: number;
+    kind?: string;
+}
+
+export interface Transporter {
+    id: string;
+    x: number;
+    y: number;
+    destinations: { map: string; spawn: number }[];
+}
+
+export interface Constants {
+    transport_radius: number;
+    door_range: number;
+}
+
+export interface MemoryStats {
+    total_bytes: number;
+    maps: { [map: string]: { grid_bytes: number; info_bytes: number } };
+}
+
+export interface NpcLocation {
+    id: string;
+    name?: string;
+    map: string;
+    x: number;
+    y: number;
+}
+"#;
+
+// Game constants that can be changed at runtime with `set_constants`
+#[derive(Serialize)]
+struct Constants {
+    transport_radius: f32,
+    door_range: f32,
+}
+
+#[derive(Deserialize)]
+struct ConstantsUpdate {
+    transport_radius: Option<f32>,
+    door_range: Option<f32>,
+}
+
+impl Default for Constants {
+    fn default() -> Self {
+        Constants {
+            transport_radius: TRANSPORT_RADIUS,
+            door_range: DOOR_REACH_DISTANCE,
+        }
+    }
+}
+
+const UNKNOWN: u8 = 1;
+const NOT_WALKABLE: u8 = 2;
+// Walkable cells store WALKABLE + the id of their connected region
+const WALKABLE: u8 = 3;
+// Region ids are stored in the cells, so there are only so many. Every region after that gets
+// this one, which doesn't tell whether two of its cells are connected.
+const SHARED_REGION: u8 = u8::MAX - WALKABLE;
+
+// Whether cells in the regions are connected, or `None` if both are in `SHARED_REGION`, which
+// doesn't tell
+fn regions_connected(a: u8, b: u8) -> Option<bool> {
+    if a == SHARED_REGION && b == SHARED_REGION {
+        None
+    } else {
+        Some(a == b)
+    }
+}
+// Size (in cells) of the tiles grids are summarized in
+const TILE_SIZE: i32 = 16;
+// Defaults for `Constants`
+// How close (in pixels) the game lets you be to a transporter to use it
+const TRANSPORT_RADIUS: f32 = 75.0;
+// How close (in pixels) the game lets you be to a door's rectangle to use it
+const DOOR_REACH_DISTANCE: f32 = 40.0;
+const RANDOM_POINT_ATTEMPTS: usize = 1000;
+
+// Cell `n` covers [n, n + 1), so game coordinates are floored (truncating would put
+// e.g. -0.5 into cell 0 instead of -1)
+fn to_grid(v: f32) -> i32 {
+    v.floor() as i32
+}
+
+// Errors from queries. In JS they're thrown as an `Error` with the variant as its `name`, e.g.
+// `e.name === "NotPrepared"`.
+#[derive(Debug)]
+pub enum QueryError {
+    // The map hasn't been prepared or loaded (yet)
+    NotPrepared(String),
+    // The point (in game coordinates) isn't on the map
+    OutOfBounds { map: String, x: f32, y: f32 },
+    // An argument couldn't be converted to what the function expects (e.g. a malformed 'G')
+    InvalidInput(String),
+}
+
+impl QueryError {
+    fn name(&self) -> &'static str {
+        match self {
+            QueryError::NotPrepared(_) => "NotPrepared",
+            QueryError::OutOfBounds { .. } => "OutOfBounds",
+            QueryError::InvalidInput(_) => "InvalidInput",
+        }
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QueryError::NotPrepared(map_name) => write!(f, "{} hasn't been prepared", map_name),
+            QueryError::OutOfBounds { map, x, y } => {
+                write!(f, "({}, {}) is outside of {}", x, y, map)
+            }
+            QueryError::InvalidInput(reason) => write!(f, "invalid input: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl From<QueryError> for JsValue {
+    fn from(e: QueryError) -> JsValue {
+        let error: JsValue = JsError::new(&e.to_string()).into();
+        reflect_set(&error, &"name".into(), &e.name().into());
+        error
+    }
+}
+
+// Values are passed to and from JS like they would be as JSON: maps become plain objects and
+// `None` becomes `null`
+fn to_js<T: Serialize + ?Sized>(value: &T) -> JsValue {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .unwrap()
+}
+
+fn from_js<T: DeserializeOwned>(value: &JsValue) -> Result<T, QueryError> {
+    serde_wasm_bindgen::from_value(value.clone())
+        .map_err(|e| QueryError::InvalidInput(e.to_string()))
+}
+
+fn get_grid(map_name: &str) -> Result<Arc<Grid>, QueryError> {
+    GRIDS
+        .read()
+        .unwrap()
+        .get(map_name)
+        .cloned()
+        .ok_or_else(|| QueryError::NotPrepared(map_name.to_string()))
+}
+
+// Points off the map throw instead of answering like they're in a wall, so a wrong map or bad
+// coordinates don't go unnoticed
+fn on_grid(grid: &Grid, map_name: &str, x: f32, y: f32) -> Result<(), QueryError> {
+    if grid.contains(x, y) {
+        Ok(())
+    } else {
+        Err(QueryError::OutOfBounds {
+            map: map_name.to_string(),
+            x,
+            y,
+        })
+    }
+}
+
+fn get_map(map_name: &str) -> Result<Arc<MapInfo>, QueryError> {
+    MAPS.read()
+        .unwrap()
+        .get(map_name)
+        .cloned()
+        .ok_or_else(|| QueryError::NotPrepared(map_name.to_string()))
+}
+
+/// Returns `true` if the map has been prepared (or loaded). Without a map, returns `true` if
+/// any map has.
+#[wasm_bindgen]
+pub fn is_prepared(map_name: Option<String>) -> bool {
+    let maps = MAPS.read().unwrap();
+    match map_name {
+        None => !maps.is_empty(),
+        Some(v) => maps.contains_key(&v),
+    }
+}
+
+/// Returns the names of the prepared maps
+#[wasm_bindgen]
+pub fn get_map_list() -> Vec<String> {
+    let maps = MAPS.read().unwrap();
+
+    let mut map_names: Vec<String> = maps.keys().cloned().collect();
+    map_names.sort();
+    map_names
+}
+
+/// Returns the min/max coordinates and the spawns of a prepared map
+#[wasm_bindgen(unchecked_return_type = "MapBounds")]
+pub fn get_map_bounds(map_name: &str) -> Result<JsValue, QueryError> {
+    let map = get_map(map_name)?;
+
+    Ok(to_js(&*map))
+}
+
+/// Returns a spawn of a prepared map as `{x, y}`, with the `width`, `height` and `theta` of the
+/// area it covers when 'G' has them, or `undefined` if the map doesn't have that many spawns
+#[wasm_bindgen(unchecked_return_type = "Spawn | undefined")]
+pub fn get_spawn(map_name: &str, spawn_index: usize) -> Result<JsValue, QueryError> {
+    let map = get_map(map_name)?;
+    Ok(match map.spawns.get(spawn_index) {
+        None => JsValue::UNDEFINED,
+        Some(spawn) => to_js(&Spawn::from_entry(spawn)),
+    })
+}
+
+/// Returns a hash (as hex) of the parts of 'G' the map was prepared from. It changes when the
+/// map would be prepared differently, e.g. to tell that a saved world is out of date.
+#[wasm_bindgen]
+pub fn get_map_hash(map_name: &str) -> Result<String, QueryError> {
+    Ok(format!("{:016x}", get_map(map_name)?.hash))
+}
+
+/// Returns a random walkable point on the map, or `undefined` if none was found
+#[wasm_bindgen(unchecked_return_type = "Point | undefined")]
+pub fn get_random_walkable_point(map_name: &str) -> Result<JsValue, QueryError> {
+    let grid = get_grid(map_name)?;
+
+    for _ in 0..RANDOM_POINT_ATTEMPTS {
+        let x = grid.min_x + (random() * grid.width as f64) as i32;
+        let y = grid.min_y + (random() * grid.height() as f64) as i32;
+        if grid.is_walkable(x, y) {
+            return Ok(to_js(&Point { x, y }));
+        }
+    }
+    Ok(JsValue::UNDEFINED)
+}
+
+/// Returns a random walkable point within `radius` of (x, y), or `undefined` if none was found
+#[wasm_bindgen(unchecked_return_type = "Point | undefined")]
+pub fn get_random_walkable_point_near(
+    map_name: &str,
+    x_i: i32,
+    y_i: i32,
+    radius: f64,
+) -> Result<JsValue, QueryError> {
+    let grid = get_grid(map_name)?;
+
+    for _ in 0..RANDOM_POINT_ATTEMPTS {
+        // Uniformly distributed in the circle
+        let distance = radius * random().sqrt();
+        let angle = random() * std::f64::consts::TAU;
+        let x = x_i.saturating_add((distance * angle.cos()).round() as i32);
+        let y = y_i.saturating_add((distance * angle.sin()).round() as i32);
+        if grid.is_walkable(x, y) {
+            return Ok(to_js(&Point { x, y }));
+        }
+    }
+    Ok(JsValue::UNDEFINED)
+}
+
+/// Returns the closest walkable point to (x, y) within `max_distance`, or `undefined` if there is
+/// none. Useful for getting out of a wall's margin, e.g. after being pushed into it.
+#[wasm_bindgen(unchecked_return_type = "Point | undefined")]
+pub fn get_nearest_walkable_point(
+    map_name: &str,
+    x: f32,
+    y: f32,
+    max_distance: i32,
+) -> Result<JsValue, QueryError> {
+    let grid = get_grid(map_name)?;
+    let map = get_map(map_name)?;
+
+    // Not through a wall, which could be walkable but impossible to get to from here
+    let accept = walls::same_side(&map.walls, x, y, max_distance);
+    Ok(
+        match grid.nearest_walkable(to_grid(x), to_grid(y), max_distance, accept) {
+            None => JsValue::UNDEFINED,
+            Some((x, y)) => to_js(&Point { x, y }),
+        },
+    )
+}
+
+/// Returns `true` if both points are walkable and connected to each other on the map, i.e.
+/// there is a walking path between them
+#[wasm_bindgen]
+pub fn same_region(map_name: &str, x1: i32, y1: i32, x2: i32, y2: i32) -> Result<bool, QueryError> {
+    let grid = get_grid(map_name)?;
+    on_grid(&grid, map_name, x1 as f32, y1 as f32)?;
+    on_grid(&grid, map_name, x2 as f32, y2 as f32)?;
+
+    Ok(grid.connected(x1, y1, x2, y2))
+}
+
+/// Overrides game constants used by the range checks. Constants that aren't set keep their
+/// current value.
+#[wasm_bindgen]
+pub fn set_constants(
+    #[wasm_bindgen(unchecked_param_type = "Partial<Constants>")] constants_js: &JsValue,
+) -> Result<(), QueryError> {
+    let update: ConstantsUpdate = from_js(constants_js)?;
+
+    let mut constants = CONSTANTS.lock().unwrap();
+    if let Some(v) = update.transport_radius {
+        constants.transport_radius = v;
+    }
+    if let Some(v) = update.door_range {
+        constants.door_range = v;
+    }
+    Ok(())
+}
+
+/// Returns the game constants currently in use
+#[wasm_bindgen(unchecked_return_type = "Constants")]
+pub fn get_constants() -> JsValue {
+    let constants = CONSTANTS.lock().unwrap();
+
+    to_js(&*constants)
+}
+
+/// Returns the map's doors, indexed like they are in 'G' (with `null` for doors that couldn't be
+/// parsed). Doors with a `kind` (e.g. stairs) take you through when you walk into them.
+#[wasm_bindgen(unchecked_return_type = "(Door | null)[]")]
+pub fn get_doors(map_name: &str) -> Result<JsValue, QueryError> {
+    let map = get_map(map_name)?;
+
+    Ok(to_js(&map.doors))
+}
+
+/// Returns `true` if (x, y) is close enough to use the door at `door_index` of the map's doors
+/// in 'G'. Doors that take you through on contact (ones with a `kind`) need (x, y) to be inside
+/// of them.
+#[wasm_bindgen]
+pub fn can_use_door(map_name: &str, x: f32, y: f32, door_index: usize) -> Result<bool, QueryError> {
+    let map = get_map(map_name)?;
+
+    let door = match map.doors.get(door_index) {
+        Some(Some(v)) => v,
+        _ => return Ok(false),
+    };
+
+    // Distance to the closest point of the door's rectangle
+    let dx = ((x - door.x).abs() - door.width / 2.0).max(0.0);
+    let dy = ((y - door.y).abs() - door.height / 2.0).max(0.0);
+    if door.on_contact() {
+        return Ok(dx == 0.0 && dy == 0.0);
+    }
+    Ok(dx.hypot(dy) < CONSTANTS.lock().unwrap().door_range)
+}
+
+/// Returns the NPCs on the map that can transport you, with where they can transport you to
+#[wasm_bindgen(unchecked_return_type = "Transporter[]")]
+pub fn get_transporters(map_name: &str) -> Result<JsValue, QueryError> {
+    let map = get_map(map_name)?;
+
+    Ok(to_js(&map.transporters))
+}
+
+/// Returns everywhere the NPC can be found on the prepared maps. Roaming NPCs are placed in the
+/// middle of the area they roam.
+#[wasm_bindgen(unchecked_return_type = "NpcLocation[]")]
+pub fn get_npc_locations(npc_id: &str) -> JsValue {
+    let maps = MAPS.read().unwrap();
+
+    let mut locations: Vec<&NpcLocation> = maps
+        .values()
+        .flat_map(|map| map.npcs.iter())
+        .filter(|npc| npc.id == npc_id)
+        .collect();
+    locations.sort_by(|a, b| a.map.cmp(&b.map));
+
+    to_js(&locations)
+}
+
+/// Returns `true` if (x, y) is close enough to a transporter on the map to use it
+#[wasm_bindgen]
+pub fn can_use_transporter(map_name: &str, x: f32, y: f32) -> Result<bool, QueryError> {
+    let map = get_map(map_name)?;
+
+    let transport_radius = CONSTANTS.lock().unwrap().transport_radius;
+    Ok(map
+        .transporters
+        .iter()
+        .any(|t| (x - t.x).hypot(y - t.y) < transport_radius))
+}
+
+/// Drops every prepared map (and every location added with `add_location`) and releases the
+/// memory they used. `prepare` (or `load_world`) has to be called again before querying.
+#[wasm_bindgen]
+pub fn free_all() {
+    let mut grids = GRIDS.write().unwrap();
+    grids.clear();
+    grids.shrink_to_fit();
+
+    let mut maps = MAPS.write().unwrap();
+    maps.clear();
+    maps.shrink_to_fit();
+
+    locations::free_all();
+    #[cfg(feature = "prepare")]
+    prepare::free_all();
+}
+
+#[derive(Serialize)]
+pub struct MapMemoryStats {
+    pub grid_bytes: usize,
+    pub info_bytes: usize,
+}
+
+#[derive(Serialize)]
+pub struct MemoryStats {
+    pub total_bytes: usize,
+    pub maps: HashMap<String, MapMemoryStats>,
+}
+
+// For use from Rust, e.g. the precompute tool
+pub fn memory_stats() -> MemoryStats {
+    let grids = GRIDS.read().unwrap();
+    let maps = MAPS.read().unwrap();
+
+    let mut stats = MemoryStats {
+        total_bytes: 0,
+        maps: HashMap::new(),
+    };
+    for (map_name, grid) in grids.iter() {
+        let map_stats = MapMemoryStats {
+            grid_bytes: std::mem::size_of::<Grid>()
+                + grid.data.capacity()
+                + grid.tiles.capacity() * std::mem::size_of::<Tile>(),
+            info_bytes: maps.get(map_name).map_or(0, |m| m.size_of()),
+        };
+        stats.total_bytes += map_stats.grid_bytes + map_stats.info_bytes;
+        stats.maps.insert(map_name.to_string(), map_stats);
+    }
+    stats
+}
+
+/// Returns an estimate of the memory used by each prepared map
+#[wasm_bindgen(unchecked_return_type = "MemoryStats")]
+pub fn get_memory_stats() -> JsValue {
+    to_js(&memory_stats())
+}
+
+#[wasm_bindgen]
+pub fn is_walkable(map_name: &str, x_i: i32, y_i: i32) -> Result<bool, QueryError> {
+    let grid = get_grid(map_name)?;
+    on_grid(&grid, map_name, x_i as f32, y_i as f32)?;
+    Ok(grid.is_walkable(x_i, y_i))
+}
+
+/// Returns `true` if (x, y) is on the map. The other queries throw an `OutOfBounds` error for
+/// points that aren't.
+#[wasm_bindgen]
+pub fn contains(map_name: &str, x: f32, y: f32) -> Result<bool, QueryError> {
+    Ok(get_grid(map_name)?.contains(x, y))
+}
+
+/// Same as `is_walkable`, but takes game coordinates as they are (e.g. `character.x`)
+#[wasm_bindgen]
+pub fn is_walkable_f32(map_name: &str, x: f32, y: f32) -> Result<bool, QueryError> {
+    let grid = get_grid(map_name)?;
+    on_grid(&grid, map_name, x, y)?;
+    Ok(grid.is_walkable(to_grid(x), to_grid(y)))
+}
+
+/// Returns `true` if you can walk in a straight line from (x1, y1) to (x2, y2)
+#[wasm_bindgen]
+pub fn can_walk_path(
+    map_name: &str,
+    x1: i32,
+    y1: i32,
+    x2: i32,
+    y2: i32,
+) -> Result<bool, QueryError> {
+    let grid = get_grid(map_name)?;
+    on_grid(&grid, map_name, x1 as f32, y1 as f32)?;
+    on_grid(&grid, map_name, x2 as f32, y2 as f32)?;
+    Ok(grid.can_walk_path(x1, y1, x2, y2))
+}
+
+/// Same as `can_walk_path`, but takes game coordinates as they are (e.g. `character.x`)
+#[wasm_bindgen]
+pub fn can_walk_path_f32(
+    map_name: &str,
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+) -> Result<bool, QueryError> {
+    let grid = get_grid(map_name)?;
+    on_grid(&grid, map_name, x1, y1)?;
+    on_grid(&grid, map_name, x2, y2)?;
+    Ok(grid.can_walk_path(to_grid(x1), to_grid(y1), to_grid(x2), to_grid(y2)))
+}
+
+/// Checks many lines at once. `segments` is `[x1, y1, x2, y2, x1, y1, x2, y2, ...]` in game
+/// coordinates, and the result has a 1 (can walk) or 0 (can't) for each group of four. Lines to
+/// or from points off the map get a 0 rather than throwing, so one bad line doesn't lose the
+/// answers for the rest.
+#[wasm_bindgen]
+pub fn can_walk_paths(map_name: &str, segments: &[f32]) -> Result<Vec<u8>, QueryError> {
+    let grid = get_grid(map_name)?;
+    Ok(segments
+        .chunks_exact(4)
+        .map(|s| {
+            let walkable =
+                grid.can_walk_path(to_grid(s[0]), to_grid(s[1]), to_grid(s[2]), to_grid(s[3]));
+            walkable as u8
+        })
+        .collect())
+}
+
+#[cfg(all(test, feature = "prepare"))]
+mod tests {
+    use super::testing::{random_map, Rng, MAX_X, MAX_Y, MIN_X, MIN_Y};
+    use super::*;
+    use std::collections::VecDeque;
+
+    // Whether there's a walking path between the points, by flooding the map from the first one
+    fn bfs(grid: &Grid, a: (i32, i32), b: (i32, i32)) -> bool {
+        if !grid.is_walkable(a.0, a.1) || !grid.is_walkable(b.0, b.1) {
+            return false;
+        }
+        let mut seen = vec![false; grid.data.len()];
+        seen[grid.index(a.0, a.1).unwrap()] = true;
+        let mut queue = VecDeque::from([a]);
+        while let Some((x, y)) = queue.pop_front() {
+            if (x, y) == b {
+                return true;
+            }
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if grid.is_walkable(nx, ny) && !seen[grid.index(nx, ny).unwrap()] {
+                    seen[grid.index(nx, ny).unwrap()] = true;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn same_region_matches_bfs() {
+        for seed in 0..8 {
+            let map_name = format!("test_same_region_{}", seed);
+            let mut rng = Rng::new(200 + seed);
+            let grid = random_map(&map_name, &mut rng);
+            for _ in 0..40 {
+                let a = (rng.range(MIN_X, MAX_X), rng.range(MIN_Y, MAX_Y));
+                let b = (rng.range(MIN_X, MAX_X), rng.range(MIN_Y, MAX_Y));
+                assert_eq!(
+                    same_region(&map_name, a.0, a.1, b.0, b.1).unwrap(),
+                    bfs(&grid, a, b),
+                    "{} {:?} -> {:?}",
+                    map_name,
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn same_region_past_the_last_region_id() {
+        // 300 rooms side by side, more than there are region ids
+        let rooms = 300;
+        let x_lines: Vec<String> = (0..=rooms).map(|i| format!("[{},0,100]", i * 40)).collect();
+        let spawns: Vec<String> = (0..rooms)
+            .map(|i| format!("[{},50]", i * 40 + 20))
+            .collect();
+        let g = format!(
+            r#"{{"geometry":{{"test_many_rooms":{{"min_x":0,"max_x":{},"min_y":0,"max_y":100,"x_lines":[{}]}}}},"maps":{{"test_many_rooms":{{"name":"test_many_rooms","spawns":[{}]}}}}}}"#,
+            rooms * 40,
+            x_lines.join(","),
+            spawns.join(",")
+        );
+        prepare_from_json(&g, None).unwrap();
+        let grid = get_grid("test_many_rooms").unwrap();
+
+        for (a, b) in [
+            (0, 1),
+            (250, 251),
+            (260, 280),
+            (260, 260),
+            (299, 299),
+            (10, 290),
+        ] {
+            let (x1, x2) = (a * 40 + 20, b * 40 + 20);
+            assert_eq!(
+                same_region("test_many_rooms", x1, 20, x2, 80).unwrap(),
+                a == b,
+                "rooms {} and {}",
+                a,
+                b
+            );
+            assert_eq!(
+                grid.can_walk_path(x1, 50, x2, 50),
+                a == b,
+                "rooms {} and {}",
+                a,
+                b
+            );
+        }
+    }
+}
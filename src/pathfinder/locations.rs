@@ -0,0 +1,110 @@
+use super::{get_map, path_steps, shortest_path, to_js, FindPathOptions, PathPoint, QueryError};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use wasm_bindgen::prelude::*;
+
+// Places bots go to by name, so scripts don't have to hard-code coordinates
+
+// Names for NPCs in 'G' that are easier to remember than their ids. Any NPC id works as a name
+// too.
+const NPC_ALIASES: &[(&str, &str)] = &[
+    ("upgrade", "newupgrade"),
+    ("compound", "newupgrade"),
+    ("potions", "fancypots"),
+];
+
+lazy_static! {
+    // Added with `add_location`, by name, then by map
+    static ref LOCATIONS: RwLock<HashMap<String, HashMap<String, PathPoint>>> =
+        RwLock::new(HashMap::new());
+}
+
+// For use from Rust. Where the location is on the map, if it's there. Locations added with
+// `add_location` come first, then NPCs, then doors by the map they lead to.
+pub fn location_on_map(map_name: &str, location: &str) -> Result<Option<PathPoint>, QueryError> {
+    let map = get_map(map_name)?;
+
+    let added = LOCATIONS
+        .read()
+        .unwrap()
+        .get(location)
+        .and_then(|maps| maps.get(map_name))
+        .copied();
+    if added.is_some() {
+        return Ok(added);
+    }
+
+    let npc_id = NPC_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == location)
+        .map_or(location, |(_, npc_id)| npc_id);
+    let npc = map
+        .npcs
+        .iter()
+        .find(|npc| npc.id == npc_id)
+        .map(|npc| PathPoint { x: npc.x, y: npc.y });
+    if npc.is_some() {
+        return Ok(npc);
+    }
+
+    // Other maps are where their door is, e.g. "bank" on main
+    Ok(map
+        .doors
+        .iter()
+        .flatten()
+        .find(|door| door.map == location)
+        .map(|door| PathPoint {
+            x: door.x,
+            y: door.y,
+        }))
+}
+
+/// Adds (or moves) a named location on a map, e.g. a fishing spot, for `find_path_to_location`
+#[wasm_bindgen]
+pub fn add_location(location: &str, map_name: &str, x: f32, y: f32) {
+    LOCATIONS
+        .write()
+        .unwrap()
+        .entry(location.to_string())
+        .or_default()
+        .insert(map_name.to_string(), PathPoint { x, y });
+}
+
+/// Returns where a named location is on the map, or `undefined` if it isn't there. Names are
+/// ones added with `add_location`, NPC ids from 'G' (e.g. "newupgrade" or "exchange"),
+/// "upgrade", "compound" and "potions", or the name of a map a door leads to (e.g. "bank").
+#[wasm_bindgen(unchecked_return_type = "Point | undefined")]
+pub fn get_location(map_name: &str, location: &str) -> Result<JsValue, QueryError> {
+    Ok(match location_on_map(map_name, location)? {
+        None => JsValue::UNDEFINED,
+        Some(point) => to_js(&point),
+    })
+}
+
+/// Same as `find_path`, but to a named location on the same map (see `get_location`). Returns
+/// `undefined` if the location isn't on the map or can't be walked to.
+#[wasm_bindgen(unchecked_return_type = "PathStep[] | undefined")]
+pub fn find_path_to_location(
+    map_name: &str,
+    x: f32,
+    y: f32,
+    location: &str,
+    #[wasm_bindgen(unchecked_param_type = "FindPathOptions")] options_js: Option<JsValue>,
+) -> Result<JsValue, QueryError> {
+    let goal = match location_on_map(map_name, location)? {
+        None => return Ok(JsValue::UNDEFINED),
+        Some(v) => v,
+    };
+    let options = FindPathOptions::from_js(options_js)?;
+    Ok(
+        match shortest_path(map_name, x, y, goal.x, goal.y, &options)? {
+            None => JsValue::UNDEFINED,
+            Some(path) => to_js(&path_steps(&path)),
+        },
+    )
+}
+
+pub(super) fn free_all() {
+    LOCATIONS.write().unwrap().clear();
+}
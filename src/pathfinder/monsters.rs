@@ -0,0 +1,52 @@
+use super::{
+    get_map, path_length, path_steps, shortest_path_to_area, to_js, FindPathOptions, GoalArea,
+    PathPoint, QueryError,
+};
+use wasm_bindgen::prelude::*;
+
+// For use from Rust. The shortest path to a walkable point where the monster type spawns on the
+// map, or `None` if it doesn't spawn there or none of its spawns can be walked to.
+pub fn path_to_monster(
+    map_name: &str,
+    x: f32,
+    y: f32,
+    monster: &str,
+    options: &FindPathOptions,
+) -> Result<Option<Vec<PathPoint>>, QueryError> {
+    let map = get_map(map_name)?;
+
+    let mut best: Option<(f32, Vec<PathPoint>)> = None;
+    for area in map.monsters.iter().filter(|area| area.kind == monster) {
+        let rect = GoalArea::Rect(area.x1, area.y1, area.x2, area.y2);
+        let path = match shortest_path_to_area(map_name, x, y, &rect, options)? {
+            None => continue,
+            Some(v) => v,
+        };
+        let length = path_length(&path);
+        if best
+            .as_ref()
+            .is_none_or(|(best_length, _)| length < *best_length)
+        {
+            best = Some((length, path));
+        }
+    }
+    Ok(best.map(|(_, path)| path))
+}
+
+/// Same as `find_path`, but to the closest walkable point where the monster type (e.g. "goo")
+/// spawns on the map. Returns `undefined` if it doesn't spawn there, or none of its spawns can
+/// be walked to.
+#[wasm_bindgen(unchecked_return_type = "PathStep[] | undefined")]
+pub fn find_path_to_monster(
+    map_name: &str,
+    x: f32,
+    y: f32,
+    monster: &str,
+    #[wasm_bindgen(unchecked_param_type = "FindPathOptions")] options_js: Option<JsValue>,
+) -> Result<JsValue, QueryError> {
+    let options = FindPathOptions::from_js(options_js)?;
+    Ok(match path_to_monster(map_name, x, y, monster, &options)? {
+        None => JsValue::UNDEFINED,
+        Some(path) => to_js(&path_steps(&path)),
+    })
+}
@@ -0,0 +1,128 @@
+use super::{from_js, get_grid, get_map, Grid, QueryError, GRIDS, NOT_WALKABLE, UNKNOWN, WALKABLE};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_MAP_OVERRIDES: &'static str = r#"
+export interface MapOverrides {
+    block?: [number, number, number, number][];
+    walkable?: [number, number, number, number][];
+}
+"#;
+
+// Hand-made fixes for a map's grid, for geometry that `prepare` gets wrong. Rects are
+// [x1, y1, x2, y2] in game coordinates.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct MapOverrides {
+    // Never walkable
+    pub block: Vec<[f32; 4]>,
+    // Walkable, as long as they connect to the rest of the map
+    pub walkable: Vec<[f32; 4]>,
+}
+
+fn fill_rect(grid: &mut Grid, rect: &[f32; 4], value: u8) {
+    grid.fill(
+        rect[0].floor() as i64,
+        rect[1].floor() as i64,
+        rect[2].ceil() as i64,
+        rect[3].ceil() as i64,
+        value,
+    );
+}
+
+pub fn apply_overrides(map_name: &str, overrides: &MapOverrides) -> Result<(), QueryError> {
+    let grid = get_grid(map_name)?;
+    let map = get_map(map_name)?;
+
+    // Forget the regions, changed cells can join or split them
+    let mut grid = Grid {
+        width: grid.width,
+        min_x: grid.min_x,
+        min_y: grid.min_y,
+        data: grid
+            .data
+            .iter()
+            .map(|v| if *v >= WALKABLE { UNKNOWN } else { *v })
+            .collect(),
+        tiles: Vec::new(),
+    };
+    for rect in &overrides.walkable {
+        fill_rect(&mut grid, rect, UNKNOWN);
+    }
+    for rect in &overrides.block {
+        fill_rect(&mut grid, rect, NOT_WALKABLE);
+    }
+    grid.fill_regions(&map.spawns);
+    grid.summarize();
+
+    GRIDS
+        .write()
+        .unwrap()
+        .insert(map_name.to_string(), Arc::new(grid));
+    Ok(())
+}
+
+/// Changes prepared maps with `{[map]: {block: [[x1, y1, x2, y2], ...], walkable: [...]}}`.
+/// Blocked rects win over walkable ones. Preparing a map again drops its overrides. Throws
+/// `NotPrepared`, without changing any map, if one of the maps isn't prepared.
+#[wasm_bindgen]
+pub fn add_overrides(
+    #[wasm_bindgen(unchecked_param_type = "{ [map: string]: MapOverrides }")]
+    overrides_js: &JsValue,
+) -> Result<(), QueryError> {
+    // Sorted, so the maps are always changed in the same order
+    let overrides: BTreeMap<String, MapOverrides> = from_js(overrides_js)?;
+    // Check every map first, so a missing one doesn't leave the others half changed
+    for map_name in overrides.keys() {
+        get_grid(map_name)?;
+        get_map(map_name)?;
+    }
+    for (map_name, map_overrides) in &overrides {
+        apply_overrides(map_name, map_overrides)?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "prepare"))]
+mod tests {
+    use super::super::{prepare_from_json, same_region};
+    use super::*;
+
+    // Two rooms, on either side of a wall at x = 0
+    fn two_rooms(map_name: &str) {
+        let g = format!(
+            r#"{{"geometry":{{"{map}":{{"min_x":-100,"max_x":100,"min_y":-50,"max_y":50,"x_lines":[[0,-50,50]]}}}},"maps":{{"{map}":{{"name":"{map}","spawns":[[-50,0],[50,0]]}}}}}}"#,
+            map = map_name
+        );
+        prepare_from_json(&g, None).unwrap();
+    }
+
+    #[test]
+    fn walkable_overrides_join_regions() {
+        two_rooms("test_overrides_join");
+        assert!(!same_region("test_overrides_join", -50, 0, 50, 0).unwrap());
+
+        let overrides = MapOverrides {
+            walkable: vec![[-10.0, -20.0, 10.0, 20.0]],
+            ..MapOverrides::default()
+        };
+        apply_overrides("test_overrides_join", &overrides).unwrap();
+        assert!(same_region("test_overrides_join", -50, 0, 50, 0).unwrap());
+    }
+
+    #[test]
+    fn huge_overrides_are_clamped_to_the_map() {
+        two_rooms("test_overrides_huge");
+
+        let overrides = MapOverrides {
+            block: vec![[-1e30, -1e30, 1e30, 1e30]],
+            ..MapOverrides::default()
+        };
+        apply_overrides("test_overrides_huge", &overrides).unwrap();
+        let grid = get_grid("test_overrides_huge").unwrap();
+        assert!(grid.data.iter().all(|cell| *cell == NOT_WALKABLE));
+    }
+}
@@ -0,0 +1,241 @@
+use super::{from_js, get_grid, to_grid, to_js, Grid, PathPoint, QueryError};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+// Working on paths that have already been found (by `find_path` or anything else)
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_PATH_STEP: &'static str = r#"
+export interface PathStep extends Point {
+    cost: number;
+}
+"#;
+
+// A point on a path, with how far (in pixels) it is to walk there from the start
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct PathStep {
+    pub x: f32,
+    pub y: f32,
+    pub cost: f32,
+}
+
+fn can_walk(grid: &Grid, a: PathPoint, b: PathPoint) -> bool {
+    grid.can_walk_path(to_grid(a.x), to_grid(a.y), to_grid(b.x), to_grid(b.y))
+}
+
+// How far the point is from the line through a and b
+fn distance_to_line(point: PathPoint, a: PathPoint, b: PathPoint) -> f32 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length = dx.hypot(dy);
+    if length == 0.0 {
+        return (point.x - a.x).hypot(point.y - a.y);
+    }
+    (dy * (point.x - a.x) - dx * (point.y - a.y)).abs() / length
+}
+
+// Ramer-Douglas-Peucker, only dropping points when the straight line that replaces them can
+// still be walked
+fn simplify(grid: &Grid, path: &[PathPoint], epsilon: f32, keep: &mut Vec<PathPoint>) {
+    let (first, last) = (path[0], path[path.len() - 1]);
+    let farthest = (1..path.len() - 1).max_by(|a, b| {
+        let a = distance_to_line(path[*a], first, last);
+        let b = distance_to_line(path[*b], first, last);
+        a.total_cmp(&b)
+    });
+    match farthest {
+        Some(i)
+            if distance_to_line(path[i], first, last) > epsilon || !can_walk(grid, first, last) =>
+        {
+            simplify(grid, &path[..=i], epsilon, keep);
+            simplify(grid, &path[i..], epsilon, keep);
+        }
+        _ => keep.push(last),
+    }
+}
+
+// For use from Rust. Drops points that are less than `epsilon` pixels off the line between the
+// points around them.
+pub fn simplified_path(
+    map_name: &str,
+    path: &[PathPoint],
+    epsilon: f32,
+) -> Result<Vec<PathPoint>, QueryError> {
+    let grid = get_grid(map_name)?;
+    if path.len() < 3 {
+        return Ok(path.to_vec());
+    }
+    let mut simplified = vec![path[0]];
+    simplify(&grid, path, epsilon, &mut simplified);
+    Ok(simplified)
+}
+
+/// Returns the path (e.g. from `find_path`) with fewer points, dropping the ones that are less
+/// than `epsilon` pixels off a straight line you can still walk. Each point left is one less
+/// `move` to make.
+#[wasm_bindgen(unchecked_return_type = "Point[]")]
+pub fn simplify_path(
+    map_name: &str,
+    #[wasm_bindgen(unchecked_param_type = "Point[]")] path_js: &JsValue,
+    epsilon: f32,
+) -> Result<JsValue, QueryError> {
+    let path: Vec<PathPoint> = from_js(path_js)?;
+
+    Ok(to_js(&simplified_path(map_name, &path, epsilon)?))
+}
+
+// For use from Rust. Splits straight lines longer than `max_segment_length` pixels into equal
+// parts that aren't. Parts shorter than a pixel would only add points (millions of them for tiny
+// lengths), so `max_segment_length` has to be at least 1.
+pub fn split_path(
+    path: &[PathPoint],
+    max_segment_length: f32,
+) -> Result<Vec<PathPoint>, QueryError> {
+    if max_segment_length.is_nan() || max_segment_length < 1.0 {
+        return Err(QueryError::InvalidInput(format!(
+            "max_segment_length has to be at least 1, not {}",
+            max_segment_length
+        )));
+    }
+    let mut split = path[..path.len().min(1)].to_vec();
+    for segment in path.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        let parts = ((b.x - a.x).hypot(b.y - a.y) / max_segment_length)
+            .ceil()
+            .max(1.0);
+        for i in 1..parts as usize {
+            let t = i as f32 / parts;
+            split.push(PathPoint {
+                x: a.x + (b.x - a.x) * t,
+                y: a.y + (b.y - a.y) * t,
+            });
+        }
+        split.push(b);
+    }
+    Ok(split)
+}
+
+// For use from Rust. How far it is to walk the path, in pixels
+pub fn path_length(path: &[PathPoint]) -> f32 {
+    path.windows(2)
+        .map(|s| (s[1].x - s[0].x).hypot(s[1].y - s[0].y))
+        .sum()
+}
+
+// For use from Rust. The path's points with the cost to walk to each of them from the start
+pub fn path_steps(path: &[PathPoint]) -> Vec<PathStep> {
+    let mut cost = 0.0;
+    let mut previous = path.first().copied();
+    path.iter()
+        .map(|&point| {
+            if let Some(p) = previous {
+                cost += (point.x - p.x).hypot(point.y - p.y);
+            }
+            previous = Some(point);
+            PathStep {
+                x: point.x,
+                y: point.y,
+                cost,
+            }
+        })
+        .collect()
+}
+
+// For use from Rust. The index of the first segment (from `path[i]` to `path[i + 1]`) that
+// can't be walked, if there is one.
+pub fn first_invalid_segment(
+    map_name: &str,
+    path: &[PathPoint],
+) -> Result<Option<usize>, QueryError> {
+    let grid = get_grid(map_name)?;
+    Ok(path
+        .windows(2)
+        .position(|segment| !can_walk(&grid, segment[0], segment[1])))
+}
+
+/// Checks a path (e.g. one found earlier with `find_path`) against the map as it is now,
+/// including overrides added since. Returns the index `i` of the first segment (from `path[i]`
+/// to `path[i + 1]`) that can't be walked anymore, or `undefined` if the whole path is fine.
+#[wasm_bindgen]
+pub fn validate_path(
+    map_name: &str,
+    #[wasm_bindgen(unchecked_param_type = "Point[]")] path_js: &JsValue,
+) -> Result<Option<u32>, QueryError> {
+    let path: Vec<PathPoint> = from_js(path_js)?;
+
+    Ok(first_invalid_segment(map_name, &path)?.map(|i| i as u32))
+}
+
+#[cfg(all(test, feature = "prepare"))]
+mod tests {
+    use super::super::{prepare_from_json, shortest_path, FindPathOptions};
+    use super::*;
+
+    fn point(x: f32, y: f32) -> PathPoint {
+        PathPoint { x, y }
+    }
+
+    #[test]
+    fn split_paths_keep_their_shape() {
+        let path = [point(0.0, 0.0), point(100.0, 0.0), point(100.0, 10.0)];
+        let split = split_path(&path, 30.0).unwrap();
+
+        // 100px in 4 parts, and 10px left as it is
+        assert_eq!(split.len(), 6);
+        assert_eq!(split[0], path[0]);
+        assert_eq!(split[4], path[1]);
+        assert_eq!(split[5], path[2]);
+        for s in split.windows(2) {
+            assert!((s[1].x - s[0].x).hypot(s[1].y - s[0].y) <= 30.0);
+        }
+        assert!((path_length(&split) - path_length(&path)).abs() < 1e-3);
+
+        assert_eq!(split_path(&path, 1.0).unwrap().len(), 111);
+        assert_eq!(split_path(&path[..1], 1.0).unwrap(), path[..1]);
+        assert!(split_path(&[], 1.0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn split_paths_need_segments_of_at_least_a_pixel() {
+        let path = [point(0.0, 0.0), point(100.0, 0.0)];
+        for max_segment_length in [0.999, 1e-6, 0.0, -1.0, f32::NAN] {
+            assert!(matches!(
+                split_path(&path, max_segment_length),
+                Err(QueryError::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn split_paths_cost_the_same_to_walk() {
+        let g = r#"{"geometry":{"test_split_path":{"min_x":-100,"max_x":300,"min_y":-100,"max_y":300,"x_lines":[[100,-100,200]]}},"maps":{"test_split_path":{"name":"test_split_path","spawns":[[0,0]]}}}"#;
+        prepare_from_json(g, None).unwrap();
+
+        let options = FindPathOptions::default();
+        let path = shortest_path("test_split_path", 0.0, 0.0, 200.0, 0.0, &options)
+            .unwrap()
+            .unwrap();
+        let split_options = FindPathOptions {
+            max_segment_length: Some(25.0),
+            ..FindPathOptions::default()
+        };
+        let split = shortest_path("test_split_path", 0.0, 0.0, 200.0, 0.0, &split_options)
+            .unwrap()
+            .unwrap();
+        assert!(split.len() > path.len());
+
+        let steps = path_steps(&split);
+        assert_eq!(steps[0].cost, 0.0);
+        for s in steps.windows(2) {
+            assert!(s[1].cost > s[0].cost && s[1].cost - s[0].cost <= 25.0 + 1e-3);
+        }
+        let cost = steps.last().unwrap().cost;
+        assert!((cost - path_steps(&path).last().unwrap().cost).abs() < 1e-2);
+        assert!((cost - path_length(&split)).abs() < 1e-3);
+
+        let too_short = FindPathOptions {
+            max_segment_length: Some(0.5),
+            ..FindPathOptions::default()
+        };
+        assert!(shortest_path("test_split_path", 0.0, 0.0, 200.0, 0.0, &too_short).is_err());
+    }
+}
@@ -0,0 +1,317 @@
+use super::{get_grid, to_js, Grid, QueryError, WALKABLE};
+use serde::Serialize;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_WALKABLE_POLYGON: &'static str = r#"
+export interface WalkablePolygon {
+    region: number;
+    exterior: [number, number][];
+    holes: [number, number][][];
+}
+"#;
+
+// The outline of one connected walkable area, in game coordinates. Rings aren't closed (the
+// last point connects back to the first). Exteriors go clockwise on screen (y pointing down),
+// holes go counterclockwise.
+#[derive(Serialize)]
+pub struct WalkablePolygon {
+    // Areas past the 252nd on a map all get the last id, so it doesn't tell them apart
+    pub region: u8,
+    pub exterior: Vec<[i32; 2]>,
+    pub holes: Vec<Vec<[i32; 2]>>,
+}
+
+// A cell side between a walkable cell and a cell that isn't, directed so the walkable cell is
+// on its right
+struct Edge {
+    from: (i32, i32),
+    direction: (i32, i32),
+}
+
+impl Edge {
+    fn to(&self) -> (i32, i32) {
+        (
+            self.from.0 + self.direction.0,
+            self.from.1 + self.direction.1,
+        )
+    }
+}
+
+fn is_walkable_cell(grid: &Grid, x: i32, y: i32) -> bool {
+    x >= 0
+        && y >= 0
+        && x < grid.width
+        && y < grid.height()
+        && grid.data[(y * grid.width + x) as usize] >= WALKABLE
+}
+
+fn boundary_edges(grid: &Grid) -> Vec<Edge> {
+    let mut edges = Vec::new();
+    for y in 0..grid.height() {
+        for x in 0..grid.width {
+            if !is_walkable_cell(grid, x, y) {
+                continue;
+            }
+            if !is_walkable_cell(grid, x, y - 1) {
+                edges.push(Edge {
+                    from: (x, y),
+                    direction: (1, 0),
+                });
+            }
+            if !is_walkable_cell(grid, x + 1, y) {
+                edges.push(Edge {
+                    from: (x + 1, y),
+                    direction: (0, 1),
+                });
+            }
+            if !is_walkable_cell(grid, x, y + 1) {
+                edges.push(Edge {
+                    from: (x + 1, y + 1),
+                    direction: (-1, 0),
+                });
+            }
+            if !is_walkable_cell(grid, x - 1, y) {
+                edges.push(Edge {
+                    from: (x, y + 1),
+                    direction: (0, -1),
+                });
+            }
+        }
+    }
+    edges
+}
+
+// Follows the edges into closed rings, returning the vertices where the direction changes
+fn trace_rings(edges: &[Edge]) -> Vec<Vec<(i32, i32)>> {
+    let mut outgoing: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+    for (i, edge) in edges.iter().enumerate() {
+        outgoing.entry(edge.from).or_default().push(i);
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut rings = Vec::new();
+    for first in 0..edges.len() {
+        if used[first] {
+            continue;
+        }
+
+        let mut ring_edges = Vec::new();
+        let mut current = first;
+        loop {
+            used[current] = true;
+            ring_edges.push(current);
+
+            // Where two areas touch diagonally, a vertex has two ways out. Turning right keeps
+            // to the same cell, so areas that only touch at a corner get their own rings.
+            let (dx, dy) = edges[current].direction;
+            let right = (-dy, dx);
+            let candidates = &outgoing[&edges[current].to()];
+            current = *candidates
+                .iter()
+                .find(|i| edges[**i].direction == right)
+                .unwrap_or(&candidates[0]);
+            if current == first {
+                break;
+            }
+        }
+
+        let mut ring = Vec::new();
+        let mut previous = edges[*ring_edges.last().unwrap()].direction;
+        for i in ring_edges {
+            if edges[i].direction != previous {
+                ring.push(edges[i].from);
+                previous = edges[i].direction;
+            }
+        }
+        rings.push(ring);
+    }
+    rings
+}
+
+// Twice the signed area, positive for rings that go clockwise on screen
+fn signed_area(ring: &[(i32, i32)]) -> i64 {
+    let mut area = 0;
+    for (i, (x1, y1)) in ring.iter().enumerate() {
+        let (x2, y2) = ring[(i + 1) % ring.len()];
+        area += *x1 as i64 * y2 as i64 - x2 as i64 * *y1 as i64;
+    }
+    area
+}
+
+pub(super) fn contains<T: Copy + Into<f64>>(ring: &[(T, T)], x: f64, y: f64) -> bool {
+    let mut inside = false;
+    for (i, (x1, y1)) in ring.iter().enumerate() {
+        let (x2, y2) = ring[(i + 1) % ring.len()];
+        let (x1, y1, x2, y2) = ((*x1).into(), (*y1).into(), x2.into(), y2.into());
+        if (y1 > y) != (y2 > y) && x < x1 + (y - y1) / (y2 - y1) * (x2 - x1) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+// The walkable cell on the right of a ring's first side
+fn ring_cell(ring: &[(i32, i32)]) -> (i32, i32) {
+    let (x1, y1) = ring[0];
+    let (x2, y2) = ring[1];
+    let (dx, dy) = ((x2 - x1).signum(), (y2 - y1).signum());
+    match (dx, dy) {
+        (1, 0) => (x1, y1),
+        (0, 1) => (x1 - 1, y1),
+        (-1, 0) => (x1 - 1, y1 - 1),
+        _ => (x1, y1 - 1),
+    }
+}
+
+pub fn walkable_polygons(map_name: &str) -> Result<Vec<WalkablePolygon>, QueryError> {
+    let grid = get_grid(map_name)?;
+
+    let mut exteriors = Vec::new();
+    let mut holes = Vec::new();
+    for ring in trace_rings(&boundary_edges(&grid)) {
+        let area = signed_area(&ring);
+        if area > 0 {
+            exteriors.push((area, ring));
+        } else {
+            holes.push(ring);
+        }
+    }
+    // Smallest first, so a hole goes to the innermost exterior around it
+    exteriors.sort_by_key(|(area, _)| *area);
+
+    let mut polygons: Vec<WalkablePolygon> = exteriors
+        .iter()
+        .map(|(_, ring)| {
+            let (x, y) = ring_cell(ring);
+            WalkablePolygon {
+                region: grid.data[(y * grid.width + x) as usize] - WALKABLE,
+                exterior: Vec::new(),
+                holes: Vec::new(),
+            }
+        })
+        .collect();
+    for hole in holes {
+        let (x, y) = ring_cell(&hole);
+        let (x, y) = (x as f64 + 0.5, y as f64 + 0.5);
+        if let Some(i) = exteriors.iter().position(|(_, e)| contains(e, x, y)) {
+            polygons[i].holes.push(to_game(&grid, &hole));
+        }
+    }
+    for (polygon, (_, ring)) in polygons.iter_mut().zip(&exteriors) {
+        polygon.exterior = to_game(&grid, ring);
+    }
+    Ok(polygons)
+}
+
+fn to_game(grid: &Grid, ring: &[(i32, i32)]) -> Vec<[i32; 2]> {
+    ring.iter()
+        .map(|(x, y)| [x + grid.min_x, y + grid.min_y])
+        .collect()
+}
+
+/// Returns the outlines of the walkable areas on the map, as `{region, exterior, holes}`
+/// polygons in game coordinates
+#[wasm_bindgen(unchecked_return_type = "WalkablePolygon[]")]
+pub fn get_walkable_polygons(map_name: &str) -> Result<JsValue, QueryError> {
+    Ok(to_js(&walkable_polygons(map_name)?))
+}
+
+#[derive(Serialize)]
+pub struct GeoJsonFeatureCollection {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    features: Vec<GeoJsonFeature>,
+}
+
+#[derive(Serialize)]
+struct GeoJsonFeature {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    properties: GeoJsonProperties,
+    geometry: GeoJsonPolygon,
+}
+
+#[derive(Serialize)]
+struct GeoJsonProperties {
+    map: String,
+    region: u8,
+}
+
+#[derive(Serialize)]
+struct GeoJsonPolygon {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    coordinates: Vec<Vec<[i32; 2]>>,
+}
+
+// GeoJSON rings are closed, and with y pointing up (as GeoJSON tools draw them) our exteriors
+// are counterclockwise and our holes clockwise, like GeoJSON wants
+fn close(mut ring: Vec<[i32; 2]>) -> Vec<[i32; 2]> {
+    ring.push(ring[0]);
+    ring
+}
+
+pub fn walkable_geojson(map_name: &str) -> Result<GeoJsonFeatureCollection, QueryError> {
+    let features = walkable_polygons(map_name)?
+        .into_iter()
+        .map(|polygon| GeoJsonFeature {
+            kind: "Feature",
+            properties: GeoJsonProperties {
+                map: map_name.to_string(),
+                region: polygon.region,
+            },
+            geometry: GeoJsonPolygon {
+                kind: "Polygon",
+                coordinates: std::iter::once(polygon.exterior)
+                    .chain(polygon.holes)
+                    .map(close)
+                    .collect(),
+            },
+        })
+        .collect();
+    Ok(GeoJsonFeatureCollection {
+        kind: "FeatureCollection",
+        features,
+    })
+}
+
+/// Returns the walkable areas on the map as a GeoJSON `FeatureCollection`, with a `Polygon`
+/// feature (with `map` and `region` properties) for each connected area
+#[wasm_bindgen]
+pub fn get_walkable_geojson(map_name: &str) -> Result<JsValue, QueryError> {
+    Ok(to_js(&walkable_geojson(map_name)?))
+}
+
+#[cfg(all(test, feature = "prepare"))]
+mod tests {
+    use super::super::testing::{random_map, Rng};
+    use super::*;
+
+    fn area(ring: &[[i32; 2]]) -> i64 {
+        let ring: Vec<(i32, i32)> = ring.iter().map(|p| (p[0], p[1])).collect();
+        signed_area(&ring).abs() / 2
+    }
+
+    #[test]
+    fn polygon_areas_match_walkable_cells() {
+        for seed in 0..8 {
+            let map_name = format!("test_polygons_{}", seed);
+            let mut rng = Rng::new(300 + seed);
+            let grid = random_map(&map_name, &mut rng);
+
+            let mut cells: HashMap<u8, i64> = HashMap::new();
+            for cell in grid.data.iter().filter(|cell| **cell >= WALKABLE) {
+                *cells.entry(cell - WALKABLE).or_default() += 1;
+            }
+            let mut areas: HashMap<u8, i64> = HashMap::new();
+            for polygon in walkable_polygons(&map_name).unwrap() {
+                let holes: i64 = polygon.holes.iter().map(|hole| area(hole)).sum();
+                *areas.entry(polygon.region).or_default() += area(&polygon.exterior) - holes;
+            }
+            assert!(!cells.is_empty(), "{}", map_name);
+            assert_eq!(areas, cells, "{}", map_name);
+        }
+    }
+}
@@ -0,0 +1,860 @@
+use super::{
+    from_js, log, to_js, Destination, Door, Grid, MapInfo, MonsterArea, NpcLocation, QueryError,
+    Transporter, Wall, GRIDS, MAPS, NOT_WALKABLE, UNKNOWN,
+};
+use crate::g::*;
+use core::cmp::{max, min};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use wasm_bindgen::prelude::*;
+
+// Everything needed to build the grids from 'G'. Left out of builds without the `prepare`
+// feature, which can only `load_world` a world made ahead of time.
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "(progress: PrepareProgress) => void")]
+    pub type ProgressCallback;
+
+    #[wasm_bindgen(method, js_name = call)]
+    fn call(this: &ProgressCallback, this_arg: &JsValue, progress: &JsValue);
+
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(handler: &js_sys::Function, timeout: i32);
+}
+
+// How far (in pixels) walls are grown, so characters keep their distance from them
+const BASE_H: i32 = 8;
+const BASE_V: i32 = 7;
+const BASE_VN: i32 = 2;
+
+lazy_static! {
+    static ref PENDING: Mutex<Option<PendingPrepare>> = Mutex::new(None);
+    static ref PARSE_WARNINGS: Mutex<Vec<ParseWarning>> = Mutex::new(Vec::new());
+    // `G.version` of the last 'G' maps were prepared from
+    static ref G_VERSION: Mutex<u64> = Mutex::new(0);
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &'static str = r#"
+export interface PrepareOptions {
+    include?: string[];
+    exclude?: string[];
+    exclude_instances?: boolean;
+    exclude_pvp?: boolean;
+}
+
+export interface PrepareProgress {
+    map: string;
+    index: number;
+    total: number;
+    elapsed_ms: number;
+}
+
+export interface ParseWarning {
+    map: string;
+    kind: "door" | "spawn" | "x_line" | "y_line" | "geometry";
+    index: number;
+    message: string;
+}
+"#;
+
+#[derive(Serialize, Clone)]
+pub struct ParseWarning {
+    pub map: String,
+    pub kind: &'static str,
+    pub index: usize,
+    pub message: String,
+}
+
+struct PendingPrepare {
+    g: GData,
+    map_names: Vec<String>,
+    start: instant::Instant,
+}
+
+fn get_map_npcs(g: &GData, map_name: &str, map: &GMap) -> Vec<NpcLocation> {
+    let mut npcs = Vec::new();
+    for npc in &map.npcs {
+        let name = npc
+            .name
+            .clone()
+            .or_else(|| g.npcs.get(&npc.id).and_then(|v| v.name.clone()));
+        for (x, y) in npc.locations() {
+            npcs.push(NpcLocation {
+                id: npc.id.to_string(),
+                name: name.clone(),
+                map: map_name.to_string(),
+                x,
+                y,
+            });
+        }
+    }
+    npcs
+}
+
+// Any NPC on the map with `places` in 'G' can transport you, not just "transporter"
+fn get_map_transporters(g: &GData, map: &GMap) -> Vec<Transporter> {
+    let mut transporters = Vec::new();
+    for npc in &map.npcs {
+        let places = match g.npcs.get(&npc.id).and_then(|v| v.places.as_ref()) {
+            None => continue,
+            Some(v) => v,
+        };
+        let (x, y) = match npc.locations().first() {
+            None => continue,
+            Some(v) => *v,
+        };
+
+        let mut destinations: Vec<Destination> = places
+            .iter()
+            .map(|(map, spawn)| Destination {
+                map: map.to_string(),
+                spawn: *spawn,
+            })
+            .collect();
+        destinations.sort_by(|a, b| a.map.cmp(&b.map));
+
+        transporters.push(Transporter {
+            id: npc.id.to_string(),
+            x,
+            y,
+            destinations,
+        });
+    }
+    transporters
+}
+
+// Monsters can spawn on a map from its own list, or from another map's list of boundaries
+fn get_map_monsters(g: &GData, map_name: &str) -> Vec<MonsterArea> {
+    let mut map_names: Vec<&String> = g.maps.keys().collect();
+    map_names.sort();
+
+    let mut areas: Vec<MonsterArea> = Vec::new();
+    for from_map in map_names {
+        for monster in &g.maps[from_map].monsters {
+            let boundary = monster
+                .boundary
+                .as_ref()
+                .filter(|b| from_map == map_name && b.len() >= 4)
+                .map(|b| (b[0], b[1], b[2], b[3]));
+            let boundaries = monster
+                .boundaries
+                .iter()
+                .filter(|b| b.map == map_name)
+                .map(|b| (b.x1, b.y1, b.x2, b.y2));
+            for (x1, y1, x2, y2) in boundary.into_iter().chain(boundaries) {
+                let area = MonsterArea {
+                    kind: monster.kind.to_string(),
+                    x1,
+                    y1,
+                    x2,
+                    y2,
+                };
+                if !areas.contains(&area) {
+                    areas.push(area);
+                }
+            }
+        }
+    }
+    areas
+}
+
+pub(super) fn get_walls(geometry: &GGeometry) -> Vec<Wall> {
+    let x_walls = geometry.x_lines.iter().map(|l| Wall {
+        x1: l.at,
+        y1: l.from,
+        x2: l.at,
+        y2: l.to,
+    });
+    let y_walls = geometry.y_lines.iter().map(|l| Wall {
+        x1: l.from,
+        y1: l.at,
+        x2: l.to,
+        y2: l.at,
+    });
+    x_walls.chain(y_walls).collect()
+}
+
+impl Wall {
+    // The area the wall makes non-walkable, including the margin characters keep from it, as
+    // (x1, y1, x2, y2) in game coordinates with the ends exclusive
+    pub(super) fn blocked_area(&self) -> (i32, i32, i32, i32) {
+        (
+            min(self.x1, self.x2).saturating_sub(BASE_H),
+            min(self.y1, self.y2).saturating_sub(BASE_VN),
+            max(self.x1, self.x2).saturating_add(BASE_H),
+            max(self.y1, self.y2).saturating_add(BASE_V),
+        )
+    }
+}
+
+// The number of cells in a grid with these bounds, or `None` if the bounds are empty or the grid
+// would be too big. Cells are indexed with i32s, which is plenty for any map in the game.
+pub(super) fn grid_size(geometry: &GGeometry) -> Option<usize> {
+    let width = geometry.max_x.checked_sub(geometry.min_x)?;
+    let height = geometry.max_y.checked_sub(geometry.min_y)?;
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+    width.checked_mul(height).map(|size| size as usize)
+}
+
+// Makes the grid from the walls, doors and spawns kept for the map, so it doesn't need 'G'
+fn build_grid(info: &MapInfo, size: usize) -> Grid {
+    let mut grid = Grid {
+        width: info.max_x - info.min_x,
+        min_x: info.min_x,
+        min_y: info.min_y,
+        data: vec![UNKNOWN; size],
+        tiles: Vec::new(),
+    };
+
+    // Make the walls, and the margin characters keep from them, non-walkable
+    for wall in &info.walls {
+        let (x1, y1, x2, y2) = wall.blocked_area();
+        grid.fill(x1 as i64, y1 as i64, x2 as i64, y2 as i64, NOT_WALKABLE);
+    }
+
+    // Doors often sit inside the wall margins, so clear their area for the fill to reach them
+    for door in info.doors.iter().flatten() {
+        grid.fill(
+            (door.x - door.width / 2.0).floor() as i64,
+            (door.y - door.height / 2.0).floor() as i64,
+            (door.x + door.width / 2.0).ceil() as i64,
+            (door.y + door.height / 2.0).ceil() as i64,
+            UNKNOWN,
+        );
+    }
+
+    // Fill in the walkable areas, labeling each connected area with its own region
+    grid.fill_regions(&info.spawns);
+    grid.summarize();
+    grid
+}
+
+// Returns whether the map was prepared. Maps without geometry or with invalid bounds are skipped.
+pub fn prepare_map(g: &GData, map_name: &String) -> bool {
+    // log(&format!("Preparing {}...", map_name));
+    // let start = instant::Instant::now();
+
+    // Get the data
+    let map = g.maps.get(map_name).unwrap();
+    let geometry = match g.geometry.get(map_name) {
+        None => {
+            log(&format!("Skipping {}, it has no geometry", map_name));
+            return false;
+        }
+        Some(v) => v,
+    };
+    let size = match grid_size(geometry) {
+        None => {
+            log(&format!("Skipping {}, its bounds are invalid", map_name));
+            return false;
+        }
+        Some(v) => v,
+    };
+
+    let info = MapInfo {
+        min_x: geometry.min_x,
+        max_x: geometry.max_x,
+        min_y: geometry.min_y,
+        max_y: geometry.max_y,
+        spawns: map.spawns.to_vec(),
+        doors: (0..map.doors.len() + map.doors.skipped.len())
+            .map(|i| {
+                map.doors.by_index(i).map(|door| Door {
+                    x: door.x,
+                    y: door.y,
+                    width: door.width,
+                    height: door.height,
+                    map: door.map.to_string(),
+                    spawn: door.spawn,
+                    kind: door.kind.clone(),
+                })
+            })
+            .collect(),
+        transporters: get_map_transporters(g, map),
+        npcs: get_map_npcs(g, map_name, map),
+        hash: map_hash(g, map_name),
+        walls: get_walls(geometry),
+        monsters: get_map_monsters(g, map_name),
+    };
+    let grid = build_grid(&info, size);
+
+    // Publish the finished map, the locks are only held for the insert
+    GRIDS
+        .write()
+        .unwrap()
+        .insert(map_name.to_string(), Arc::new(grid));
+    MAPS.write()
+        .unwrap()
+        .insert(map_name.to_string(), Arc::new(info));
+
+    // DEBUG Output
+    // log(&format!(
+    //     "  Prepared grid for {} in {}ms!",
+    //     map_name,
+    //     start.elapsed().as_millis()
+    // ));
+    true
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct PrepareOptions {
+    // If set, only these maps are prepared
+    include: Option<Vec<String>>,
+    // These maps are never prepared
+    exclude: Vec<String>,
+    // Skip maps flagged as instances in 'G'
+    exclude_instances: bool,
+    // Skip maps flagged as PVP in 'G'
+    exclude_pvp: bool,
+}
+
+impl PrepareOptions {
+    fn from_js(options_js: Option<JsValue>) -> Result<PrepareOptions, QueryError> {
+        match options_js {
+            None => Ok(PrepareOptions::default()),
+            Some(v) => from_js(&v),
+        }
+    }
+
+    fn wants(&self, map_name: &String, map: &GMap) -> bool {
+        let included = match &self.include {
+            None => true,
+            Some(v) => v.contains(map_name),
+        };
+        let excluded = self.exclude.contains(map_name)
+            || (self.exclude_instances && map.instance == Some(true))
+            || (self.exclude_pvp && map.pvp == Some(true));
+        included && !excluded
+    }
+}
+
+fn maps_to_prepare(g: &GData, options: &PrepareOptions) -> Vec<String> {
+    // Skip ignored maps, and maps the options filter out
+    g.maps
+        .iter()
+        .filter(|(map_name, map)| map.ignore.is_none() && options.wants(map_name, map))
+        .map(|(map_name, _)| map_name.to_string())
+        .collect()
+}
+
+fn record_parse_warnings(g: &GData) {
+    let mut warnings = Vec::new();
+    for (map_name, map) in &g.maps {
+        if map.ignore.is_none() && !g.geometry.contains_key(map_name) {
+            warnings.push(ParseWarning {
+                map: map_name.to_string(),
+                kind: "geometry",
+                index: 0,
+                message: "map has no geometry, so it wasn't prepared".to_string(),
+            });
+        }
+        let skipped = map
+            .doors
+            .skipped
+            .iter()
+            .map(|s| ("door", s))
+            .chain(map.spawns.skipped.iter().map(|s| ("spawn", s)));
+        for (kind, (index, message)) in skipped {
+            warnings.push(ParseWarning {
+                map: map_name.to_string(),
+                kind,
+                index: *index,
+                message: message.to_string(),
+            });
+        }
+    }
+    for (map_name, geometry) in &g.geometry {
+        let skipped = geometry
+            .x_lines
+            .skipped
+            .iter()
+            .map(|s| ("x_line", s))
+            .chain(geometry.y_lines.skipped.iter().map(|s| ("y_line", s)));
+        for (kind, (index, message)) in skipped {
+            warnings.push(ParseWarning {
+                map: map_name.to_string(),
+                kind,
+                index: *index,
+                message: message.to_string(),
+            });
+        }
+    }
+    warnings.sort_by(|a, b| (&a.map, a.kind, a.index).cmp(&(&b.map, b.kind, b.index)));
+
+    *PARSE_WARNINGS.lock().unwrap() = warnings;
+}
+
+fn prepare_all(
+    g: &GData,
+    options: &PrepareOptions,
+    mut on_progress: impl FnMut(&str, usize, usize),
+) {
+    record_parse_warnings(g);
+    *G_VERSION.lock().unwrap() = g.version;
+    let map_names = maps_to_prepare(g, options);
+
+    let total = map_names.len();
+    for (index, map_name) in map_names.into_iter().enumerate() {
+        // Make the grid
+        prepare_map(g, &map_name);
+        on_progress(&map_name, index, total);
+    }
+}
+
+/// Prepares every map in 'G'. `options` may be `{include: [...], exclude: [...]}` to only
+/// prepare some of the maps.
+#[wasm_bindgen]
+pub fn prepare(
+    g_js: &JsValue,
+    #[wasm_bindgen(unchecked_param_type = "PrepareOptions")] options_js: Option<JsValue>,
+) -> Result<(), QueryError> {
+    // Convert 'G' to a variable we can use
+    let g: GData = from_js(g_js)?;
+
+    let start = instant::Instant::now();
+    prepare_all(&g, &PrepareOptions::from_js(options_js)?, |_, _, _| {});
+    log(&format!(
+        "Prepared all maps in {}ms!",
+        start.elapsed().as_millis()
+    ));
+    Ok(())
+}
+
+/// Same as `prepare`, but only takes the parts of 'G' we use (`G.maps`, `G.geometry` and
+/// `G.npcs`), which avoids converting the whole object. `G.npcs` can be left out, but then NPCs
+/// only have the names their maps give them and there are no transporters.
+#[wasm_bindgen]
+pub fn prepare_maps(
+    maps_js: &JsValue,
+    geometry_js: &JsValue,
+    npcs_js: Option<JsValue>,
+    #[wasm_bindgen(unchecked_param_type = "PrepareOptions")] options_js: Option<JsValue>,
+) -> Result<(), QueryError> {
+    let g = GData {
+        geometry: from_js(geometry_js)?,
+        maps: from_js(maps_js)?,
+        npcs: match npcs_js {
+            None => HashMap::new(),
+            Some(v) => from_js(&v)?,
+        },
+        version: 0,
+    };
+
+    let start = instant::Instant::now();
+    prepare_all(&g, &PrepareOptions::from_js(options_js)?, |_, _, _| {});
+    log(&format!(
+        "Prepared all maps in {}ms!",
+        start.elapsed().as_millis()
+    ));
+    Ok(())
+}
+
+/// Same as `prepare`, but parses 'G' straight from a JSON string (e.g. `JSON.stringify(G)` or
+/// a cached file), which is much faster than converting a large JS object
+#[wasm_bindgen]
+pub fn prepare_from_json(
+    g_json: &str,
+    #[wasm_bindgen(unchecked_param_type = "PrepareOptions")] options_js: Option<JsValue>,
+) -> Result<(), QueryError> {
+    let g: GData =
+        serde_json::from_str(g_json).map_err(|e| QueryError::InvalidInput(e.to_string()))?;
+
+    let start = instant::Instant::now();
+    prepare_all(&g, &PrepareOptions::from_js(options_js)?, |_, _, _| {});
+    log(&format!(
+        "Prepared all maps in {}ms!",
+        start.elapsed().as_millis()
+    ));
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct PrepareProgress<'a> {
+    map: &'a str,
+    index: usize,
+    total: usize,
+    elapsed_ms: u64,
+}
+
+/// Same as `prepare`, but calls `callback` with `{map, index, total, elapsed_ms}` after each map
+#[wasm_bindgen]
+pub fn prepare_with_progress(
+    g_js: &JsValue,
+    callback: &ProgressCallback,
+    #[wasm_bindgen(unchecked_param_type = "PrepareOptions")] options_js: Option<JsValue>,
+) -> Result<(), QueryError> {
+    // Convert 'G' to a variable we can use
+    let g: GData = from_js(g_js)?;
+
+    let start = instant::Instant::now();
+    prepare_all(
+        &g,
+        &PrepareOptions::from_js(options_js)?,
+        |map, index, total| {
+            let progress = PrepareProgress {
+                map,
+                index,
+                total,
+                elapsed_ms: start.elapsed().as_millis() as u64,
+            };
+            callback.call(&JsValue::NULL, &to_js(&progress));
+        },
+    );
+    log(&format!(
+        "Prepared all maps in {}ms!",
+        start.elapsed().as_millis()
+    ));
+    Ok(())
+}
+
+/// Queues 'G' for preparation one map at a time with `prepare_next`, so the host can yield
+/// to the event loop between maps. Returns the number of maps queued.
+#[wasm_bindgen]
+pub fn prepare_start(
+    g_js: &JsValue,
+    #[wasm_bindgen(unchecked_param_type = "PrepareOptions")] options_js: Option<JsValue>,
+) -> Result<usize, QueryError> {
+    // Convert 'G' to a variable we can use
+    let g: GData = from_js(g_js)?;
+
+    record_parse_warnings(&g);
+    *G_VERSION.lock().unwrap() = g.version;
+    let mut map_names = maps_to_prepare(&g, &PrepareOptions::from_js(options_js)?);
+    map_names.reverse();
+    let total = map_names.len();
+
+    let mut pending = PENDING.lock().unwrap();
+    *pending = Some(PendingPrepare {
+        g,
+        map_names,
+        start: instant::Instant::now(),
+    });
+    Ok(total)
+}
+
+// Lets the event loop run (e.g. to render a frame or handle input) before continuing
+async fn yield_now() {
+    let promise = js_sys::Promise::new(&mut |resolve, _| set_timeout(&resolve, 0));
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+/// Same as `prepare`, but yields to the event loop after each map so the page stays responsive.
+/// The returned promise resolves once every map is prepared.
+#[wasm_bindgen]
+pub async fn prepare_async(
+    g_js: JsValue,
+    #[wasm_bindgen(unchecked_param_type = "PrepareOptions")] options_js: Option<JsValue>,
+) -> Result<(), QueryError> {
+    // Convert 'G' to a variable we can use
+    let g: GData = from_js(&g_js)?;
+    let options = PrepareOptions::from_js(options_js)?;
+
+    let start = instant::Instant::now();
+    record_parse_warnings(&g);
+    *G_VERSION.lock().unwrap() = g.version;
+    for map_name in maps_to_prepare(&g, &options) {
+        prepare_map(&g, &map_name);
+        yield_now().await;
+    }
+    log(&format!(
+        "Prepared all maps in {}ms!",
+        start.elapsed().as_millis()
+    ));
+    Ok(())
+}
+
+/// Prepares the next queued map. Returns `true` while there are more maps to prepare.
+#[wasm_bindgen]
+pub fn prepare_next() -> bool {
+    let mut pending = PENDING.lock().unwrap();
+    let work = match pending.as_mut() {
+        None => return false,
+        Some(v) => v,
+    };
+
+    if let Some(map_name) = work.map_names.pop() {
+        prepare_map(&work.g, &map_name);
+    }
+    if !work.map_names.is_empty() {
+        return true;
+    }
+
+    log(&format!(
+        "Prepared all maps in {}ms!",
+        work.start.elapsed().as_millis()
+    ));
+    *pending = None;
+    false
+}
+
+// For use from Rust, e.g. the precompute tool
+pub fn parse_warnings() -> Vec<ParseWarning> {
+    PARSE_WARNINGS.lock().unwrap().clone()
+}
+
+/// Returns the doors, spawns and lines that were skipped during the last `prepare` because they
+/// couldn't be parsed, and the maps skipped because they have no geometry, as
+/// `{map, kind, index, message}` objects
+#[wasm_bindgen(unchecked_return_type = "ParseWarning[]")]
+pub fn get_parse_warnings() -> JsValue {
+    let warnings = PARSE_WARNINGS.lock().unwrap();
+
+    to_js(&*warnings)
+}
+
+// FNV-1a, so a map's hash is the same on every platform and Rust version
+struct Fnv(u64);
+
+impl Fnv {
+    fn bytes(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn len(&mut self, v: usize) {
+        self.bytes(&(v as u64).to_le_bytes());
+    }
+
+    fn i32(&mut self, v: i32) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    fn f32(&mut self, v: f32) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    fn string(&mut self, v: &str) {
+        self.len(v.len());
+        self.bytes(v.as_bytes());
+    }
+
+    fn lines(&mut self, lines: &[GLine]) {
+        self.len(lines.len());
+        for line in lines {
+            self.len(3);
+            self.i32(line.at);
+            self.i32(line.from);
+            self.i32(line.to);
+        }
+    }
+}
+
+// Hashes everything in 'G' that `prepare_map` uses for the map, so the hash changes whenever
+// preparing the map again would give a different result
+pub fn map_hash(g: &GData, map_name: &str) -> u64 {
+    let mut h = Fnv(0xcbf29ce484222325);
+    h.string(map_name);
+
+    if let Some(geometry) = g.geometry.get(map_name) {
+        h.i32(geometry.min_x);
+        h.i32(geometry.max_x);
+        h.i32(geometry.min_y);
+        h.i32(geometry.max_y);
+        h.lines(&geometry.x_lines);
+        h.lines(&geometry.y_lines);
+    }
+
+    let map = match g.maps.get(map_name) {
+        None => return h.0,
+        Some(v) => v,
+    };
+
+    h.len(map.spawns.len());
+    for spawn in map.spawns.iter() {
+        h.len(spawn.len());
+        spawn.iter().for_each(|v| h.f32(*v));
+    }
+
+    let doors = map.doors.len() + map.doors.skipped.len();
+    h.len(doors);
+    for i in 0..doors {
+        match map.doors.by_index(i) {
+            None => h.len(0),
+            Some(door) => {
+                h.len(1);
+                h.f32(door.x);
+                h.f32(door.y);
+                h.f32(door.width);
+                h.f32(door.height);
+                h.string(&door.map);
+                h.len(door.spawn);
+                h.string(door.kind.as_deref().unwrap_or_default());
+            }
+        }
+    }
+
+    h.len(map.npcs.len());
+    for npc in &map.npcs {
+        h.string(&npc.id);
+        h.string(npc.name.as_deref().unwrap_or_default());
+        let locations = npc.locations();
+        h.len(locations.len());
+        for (x, y) in locations {
+            h.f32(x);
+            h.f32(y);
+        }
+
+        let g_npc = g.npcs.get(&npc.id);
+        h.string(g_npc.and_then(|v| v.name.as_deref()).unwrap_or_default());
+        let mut places: Vec<(&String, &usize)> = g_npc
+            .and_then(|v| v.places.as_ref())
+            .map(|v| v.iter().collect())
+            .unwrap_or_default();
+        places.sort();
+        h.len(places.len());
+        for (map, spawn) in places {
+            h.string(map);
+            h.len(*spawn);
+        }
+    }
+
+    let monsters = get_map_monsters(g, map_name);
+    h.len(monsters.len());
+    for area in monsters {
+        h.string(&area.kind);
+        [area.x1, area.y1, area.x2, area.y2]
+            .iter()
+            .for_each(|v| h.f32(*v));
+    }
+    h.0
+}
+
+/// Returns the hash `get_map_hash` would return for each map in 'G' once prepared, without
+/// preparing them
+#[wasm_bindgen(unchecked_return_type = "{ [map: string]: string }")]
+pub fn get_g_map_hashes(g_js: &JsValue) -> Result<JsValue, QueryError> {
+    // Convert 'G' to a variable we can use
+    let g: GData = from_js(g_js)?;
+
+    let hashes: HashMap<&String, String> = g
+        .maps
+        .keys()
+        .map(|map_name| (map_name, format!("{:016x}", map_hash(&g, map_name))))
+        .collect();
+    Ok(to_js(&hashes))
+}
+
+fn update_all(g: &GData, options: &PrepareOptions) -> Vec<String> {
+    let wanted: HashSet<String> = maps_to_prepare(g, options).into_iter().collect();
+
+    // Drop maps that aren't in 'G' anymore, or that the options leave out now
+    let removed: Vec<String> = MAPS
+        .read()
+        .unwrap()
+        .keys()
+        .filter(|m| !wanted.contains(*m))
+        .cloned()
+        .collect();
+    for map_name in &removed {
+        remove_map(map_name);
+    }
+
+    // Nothing changed if the version didn't, but maps that weren't prepared yet still need to be
+    let same_version = g.version != 0 && g.version == *G_VERSION.lock().unwrap();
+    let hashes: HashMap<String, u64> = MAPS
+        .read()
+        .unwrap()
+        .iter()
+        .map(|(map_name, info)| (map_name.to_string(), info.hash))
+        .collect();
+    let mut changed: Vec<String> = wanted
+        .into_iter()
+        .filter(|map_name| match hashes.get(map_name) {
+            None => true,
+            Some(hash) => !same_version && *hash != map_hash(g, map_name),
+        })
+        .collect();
+    changed.sort();
+
+    record_parse_warnings(g);
+    *G_VERSION.lock().unwrap() = g.version;
+    // A map that can't be prepared anymore (e.g. its bounds are invalid now) is dropped, instead
+    // of keeping its old grid
+    changed.retain(|map_name| {
+        let prepared = prepare_map(g, map_name);
+        if !prepared {
+            remove_map(map_name);
+        }
+        prepared
+    });
+    changed
+}
+
+fn remove_map(map_name: &str) {
+    GRIDS.write().unwrap().remove(map_name);
+    MAPS.write().unwrap().remove(map_name);
+}
+
+/// Updates the prepared maps to a new 'G' (e.g. after the game updated), only preparing the maps
+/// that changed or weren't prepared yet. Maps that are gone from 'G', that the options leave out,
+/// or that can't be prepared anymore are dropped. Returns the names of the maps that were
+/// prepared.
+#[wasm_bindgen]
+pub fn update(
+    g_js: &JsValue,
+    #[wasm_bindgen(unchecked_param_type = "PrepareOptions")] options_js: Option<JsValue>,
+) -> Result<Vec<String>, QueryError> {
+    // Convert 'G' to a variable we can use
+    let g: GData = from_js(g_js)?;
+
+    let start = instant::Instant::now();
+    let changed = update_all(&g, &PrepareOptions::from_js(options_js)?);
+    log(&format!(
+        "Updated {} maps in {}ms!",
+        changed.len(),
+        start.elapsed().as_millis()
+    ));
+    Ok(changed)
+}
+
+/// Same as `update`, but parses 'G' straight from a JSON string, like `prepare_from_json`
+#[wasm_bindgen]
+pub fn update_from_json(
+    g_json: &str,
+    #[wasm_bindgen(unchecked_param_type = "PrepareOptions")] options_js: Option<JsValue>,
+) -> Result<Vec<String>, QueryError> {
+    let g: GData =
+        serde_json::from_str(g_json).map_err(|e| QueryError::InvalidInput(e.to_string()))?;
+
+    let start = instant::Instant::now();
+    let changed = update_all(&g, &PrepareOptions::from_js(options_js)?);
+    log(&format!(
+        "Updated {} maps in {}ms!",
+        changed.len(),
+        start.elapsed().as_millis()
+    ));
+    Ok(changed)
+}
+
+// Drops the parse warnings and any queued preparation, for `free_all`
+pub(super) fn free_all() {
+    let mut warnings = PARSE_WARNINGS.lock().unwrap();
+    warnings.clear();
+    warnings.shrink_to_fit();
+
+    *PENDING.lock().unwrap() = None;
+    *G_VERSION.lock().unwrap() = 0;
+}
+
+#[cfg(all(test, feature = "prepare"))]
+mod tests {
+    use super::super::{get_grid, is_prepared};
+    use super::*;
+
+    #[test]
+    fn maps_without_geometry_are_skipped() {
+        let g = r#"{"geometry":{"test_with_geometry":{"min_x":0,"max_x":10,"min_y":0,"max_y":10}},"maps":{"test_with_geometry":{"name":"test_with_geometry","spawns":[[5,5]]},"test_no_geometry":{"name":"test_no_geometry","spawns":[[5,5]]}}}"#;
+        prepare_from_json(g, None).unwrap();
+        assert!(get_grid("test_with_geometry").is_ok());
+        assert!(!is_prepared(Some("test_no_geometry".to_string())));
+    }
+}
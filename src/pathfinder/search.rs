@@ -0,0 +1,1172 @@
+use super::{
+    from_js, get_grid, get_map, path_length, path_steps, polygons, regions_connected, split_path,
+    to_grid, to_js, walls, Grid, PathStep, QueryError, Wall, WALKABLE,
+};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_FIND_PATH_OPTIONS: &'static str = r#"
+export interface FindPathOptions {
+    /** At least 1, smaller values throw `InvalidInput`. */
+    max_segment_length?: number;
+    clamp_to_bounds?: boolean;
+    diagonals?: boolean;
+    /** Between 1 and 2, values outside of that are clamped. Defaults to `Math.SQRT2`. */
+    diagonal_cost?: number;
+    smooth?: boolean;
+}
+
+export interface PathQuery {
+    map: string;
+    x1: number;
+    y1: number;
+    x2: number;
+    y2: number;
+    options?: FindPathOptions;
+}
+"#;
+
+// A point on a path, in game coordinates
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct PathPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+// A cell in grid coordinates
+type Cell = (i32, i32);
+
+// A cell waiting in the open list, ordered so the `BinaryHeap` pops the lowest `f` first
+struct Open {
+    f: f64,
+    cell: Cell,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.f == other.f
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.f.total_cmp(&self.f)
+    }
+}
+
+fn distance(a: Cell, b: Cell) -> f64 {
+    ((a.0 - b.0) as f64).hypot((a.1 - b.1) as f64)
+}
+
+// The cells (in grid coordinates, inclusive) the search stops at. A single goal cell is a
+// rectangle of one.
+#[derive(Clone, Copy)]
+struct Goal<'a> {
+    x1: i32,
+    y1: i32,
+    x2: i32,
+    y2: i32,
+    // If there's a polygon (in grid coordinates), only the cells in the rectangle with their
+    // centre inside it
+    polygon: Option<&'a [(f64, f64)]>,
+}
+
+impl Goal<'_> {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x1
+            && x <= self.x2
+            && y >= self.y1
+            && y <= self.y2
+            && self
+                .polygon
+                .is_none_or(|p| polygons::contains(p, x as f64 + 0.5, y as f64 + 0.5))
+    }
+
+    // How far the cell is outside the goal on each axis
+    fn offset(&self, (x, y): Cell) -> (i32, i32) {
+        (
+            (self.x1 - x).max(x - self.x2).max(0),
+            (self.y1 - y).max(y - self.y2).max(0),
+        )
+    }
+}
+
+// A visited cell
+#[derive(Clone, Copy)]
+struct Visit {
+    // Length of the path here
+    g: f64,
+    // Where the path here comes from, the cell before it when the path is straight
+    parent: Option<Cell>,
+    // The direction the search was going when it jumped here, for pruning the next jumps
+    direction: Option<Cell>,
+}
+
+// Jump point search over a grid (in grid coordinates). Moving diagonally is only allowed when
+// both of the cells beside the move are walkable, so paths never cut the corner of a wall.
+//
+// Like Theta*, a cell is connected straight to its parent's parent when it can walk there in a
+// straight line (if `smooth`), so paths are taut instead of following the grid directions.
+struct Search<'a> {
+    grid: &'a Grid,
+    height: i32,
+    goal: Goal<'a>,
+    diagonals: bool,
+    diagonal_cost: f64,
+    smooth: bool,
+}
+
+impl Search<'_> {
+    fn walkable(&self, x: i32, y: i32) -> bool {
+        x >= 0
+            && y >= 0
+            && x < self.grid.width
+            && y < self.height
+            && self.grid.data[(y * self.grid.width + x) as usize] >= WALKABLE
+    }
+
+    fn walkable_index(&self, i: i32) -> bool {
+        self.grid.data[i as usize] >= WALKABLE
+    }
+
+    // The length of a straight move between the cells (in one of the grid directions, unless
+    // `smooth`)
+    fn cost(&self, a: Cell, b: Cell) -> f64 {
+        if self.smooth {
+            return distance(a, b);
+        }
+        let (dx, dy) = ((a.0 - b.0).abs() as f64, (a.1 - b.1).abs() as f64);
+        dx.max(dy) - dx.min(dy) + dx.min(dy) * self.diagonal_cost
+    }
+
+    // A lower bound on the length of the rest of the path from the cell
+    fn heuristic(&self, cell: Cell) -> f64 {
+        let (dx, dy) = self.goal.offset(cell);
+        if self.smooth {
+            return (dx as f64).hypot(dy as f64);
+        }
+        let (dx, dy) = (dx as f64, dy as f64);
+        if !self.diagonals {
+            return dx + dy;
+        }
+        dx.max(dy) - dx.min(dy) + dx.min(dy) * self.diagonal_cost
+    }
+
+    // Walks from (x, y) in the direction until it finds a cell that has to be looked at (the
+    // goal, or a cell with a neighbor that can't be reached more directly another way)
+    fn jump(&self, x: i32, y: i32, dx: i32, dy: i32) -> Option<Cell> {
+        if dx == 0 {
+            return self.jump_vertical(x, y, dy);
+        }
+        if dy == 0 {
+            if !self.diagonals {
+                return self.jump_horizontal_straight(x, y, dx);
+            }
+            return self.jump_horizontal(x, y, dx);
+        }
+
+        let (mut x, mut y) = (x + dx, y + dy);
+        loop {
+            if !self.walkable(x, y) {
+                return None;
+            }
+            if self.goal.contains(x, y)
+                || self.jump_horizontal(x, y, dx).is_some()
+                || self.jump_vertical(x, y, dy).is_some()
+            {
+                return Some((x, y));
+            }
+            if !self.walkable(x + dx, y) || !self.walkable(x, y + dy) {
+                return None;
+            }
+            x += dx;
+            y += dy;
+        }
+    }
+
+    // Straight jumps are where the search spends its time, so they work on indexes and only
+    // check the bounds they have to
+    fn jump_horizontal(&self, mut x: i32, y: i32, dx: i32) -> Option<Cell> {
+        let width = self.grid.width;
+        let row = y * width;
+        let above = y > 0;
+        let below = y < self.height - 1;
+        let goal_row = y >= self.goal.y1 && y <= self.goal.y2;
+        loop {
+            x += dx;
+            if x < 0 || x >= width || !self.walkable_index(row + x) {
+                return None;
+            }
+            if goal_row && self.goal.contains(x, y) {
+                return Some((x, y));
+            }
+            let behind = x - dx;
+            if (above
+                && self.walkable_index(row - width + x)
+                && !self.walkable_index(row - width + behind))
+                || (below
+                    && self.walkable_index(row + width + x)
+                    && !self.walkable_index(row + width + behind))
+            {
+                return Some((x, y));
+            }
+        }
+    }
+
+    // Without diagonals, horizontal jumps take the place of diagonal ones and stop wherever a
+    // vertical jump would find something
+    fn jump_horizontal_straight(&self, mut x: i32, y: i32, dx: i32) -> Option<Cell> {
+        loop {
+            x += dx;
+            if !self.walkable(x, y) {
+                return None;
+            }
+            if self.goal.contains(x, y)
+                || self.jump_vertical(x, y, 1).is_some()
+                || self.jump_vertical(x, y, -1).is_some()
+            {
+                return Some((x, y));
+            }
+        }
+    }
+
+    fn jump_vertical(&self, x: i32, mut y: i32, dy: i32) -> Option<Cell> {
+        let width = self.grid.width;
+        let left = x > 0;
+        let right = x < width - 1;
+        let goal_column = x >= self.goal.x1 && x <= self.goal.x2;
+        loop {
+            y += dy;
+            if y < 0 || y >= self.height {
+                return None;
+            }
+            let i = y * width + x;
+            if !self.walkable_index(i) {
+                return None;
+            }
+            if goal_column && self.goal.contains(x, y) {
+                return Some((x, y));
+            }
+            let behind = i - dy * width;
+            if (left && self.walkable_index(i - 1) && !self.walkable_index(behind - 1))
+                || (right && self.walkable_index(i + 1) && !self.walkable_index(behind + 1))
+            {
+                return Some((x, y));
+            }
+        }
+    }
+
+    // The directions worth searching from a cell, given the direction we came from
+    fn directions(&self, (x, y): Cell, direction: Option<Cell>) -> Vec<Cell> {
+        if !self.diagonals {
+            // Every way but back
+            return [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                .into_iter()
+                .filter(|(dx, dy)| direction != Some((-dx, -dy)) && self.walkable(x + dx, y + dy))
+                .collect();
+        }
+
+        let mut directions = Vec::new();
+        let (dx, dy) = match direction {
+            None => {
+                for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                    if self.walkable(x + dx, y + dy) {
+                        directions.push((dx, dy));
+                    }
+                }
+                for (dx, dy) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+                    if self.walkable(x + dx, y) && self.walkable(x, y + dy) {
+                        directions.push((dx, dy));
+                    }
+                }
+                return directions;
+            }
+            Some(v) => v,
+        };
+
+        if dx != 0 && dy != 0 {
+            let vertical = self.walkable(x, y + dy);
+            let horizontal = self.walkable(x + dx, y);
+            if vertical {
+                directions.push((0, dy));
+            }
+            if horizontal {
+                directions.push((dx, 0));
+            }
+            if vertical && horizontal {
+                directions.push((dx, dy));
+            }
+        } else if dx != 0 {
+            let next = self.walkable(x + dx, y);
+            for side in [-1, 1] {
+                if self.walkable(x, y + side) {
+                    directions.push((0, side));
+                    if next {
+                        directions.push((dx, side));
+                    }
+                }
+            }
+            if next {
+                directions.push((dx, 0));
+            }
+        } else {
+            let next = self.walkable(x, y + dy);
+            for side in [-1, 1] {
+                if self.walkable(x + side, y) {
+                    directions.push((side, 0));
+                    if next {
+                        directions.push((side, dy));
+                    }
+                }
+            }
+            if next {
+                directions.push((0, dy));
+            }
+        }
+        directions
+    }
+
+    fn line_of_sight(&self, a: Cell, b: Cell) -> bool {
+        let (min_x, min_y) = (self.grid.min_x, self.grid.min_y);
+        self.grid
+            .can_walk_path(a.0 + min_x, a.1 + min_y, b.0 + min_x, b.1 + min_y)
+    }
+
+    // Returns the cells the path turns at, from start to the first goal cell it reaches
+    fn run(&self, start: Cell) -> Option<Vec<Cell>> {
+        let mut visited: HashMap<Cell, Visit> = HashMap::new();
+        let mut open = BinaryHeap::new();
+        visited.insert(
+            start,
+            Visit {
+                g: 0.0,
+                parent: None,
+                direction: None,
+            },
+        );
+        open.push(Open {
+            f: self.heuristic(start),
+            cell: start,
+        });
+
+        while let Some(Open { f, cell }) = open.pop() {
+            let visit = visited[&cell];
+            if f > visit.g + self.heuristic(cell) {
+                // We found a shorter way here after this was queued
+                continue;
+            }
+            if self.goal.contains(cell.0, cell.1) {
+                let mut cells = vec![cell];
+                let mut parent = visit.parent;
+                while let Some(p) = parent {
+                    cells.push(p);
+                    parent = visited[&p].parent;
+                }
+                cells.reverse();
+                return Some(cells);
+            }
+
+            for (dx, dy) in self.directions(cell, visit.direction) {
+                let next = match self.jump(cell.0, cell.1, dx, dy) {
+                    None => continue,
+                    Some(v) => v,
+                };
+                let (parent, g) = match visit.parent {
+                    Some(p) if self.smooth && self.line_of_sight(p, next) => {
+                        (p, visited[&p].g + distance(p, next))
+                    }
+                    _ => (cell, visit.g + self.cost(cell, next)),
+                };
+                if visited.get(&next).is_some_and(|v| v.g <= g) {
+                    continue;
+                }
+                visited.insert(
+                    next,
+                    Visit {
+                        g,
+                        parent: Some(parent),
+                        direction: Some((dx, dy)),
+                    },
+                );
+                open.push(Open {
+                    f: g + self.heuristic(next),
+                    cell: next,
+                });
+            }
+        }
+        None
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct FindPathOptions {
+    // Longer straight lines are split into parts of at most this many pixels (at least 1)
+    pub max_segment_length: Option<f32>,
+    // Move ends that are off the map to its edge, instead of returning `OutOfBounds`
+    pub clamp_to_bounds: bool,
+    // Whether the search can move diagonally, and (without `smooth`) how much that costs
+    // compared to moving one cell straight. The cost is clamped to between 1 (a diagonal move is
+    // never shorter than a straight one) and 2 (it's never longer than the two straight moves it
+    // replaces, so jump point search stays optimal).
+    pub diagonals: bool,
+    pub diagonal_cost: f64,
+    // Connect the points of the path with straight lines at any angle where they can be walked
+    pub smooth: bool,
+}
+
+impl Default for FindPathOptions {
+    fn default() -> Self {
+        FindPathOptions {
+            max_segment_length: None,
+            clamp_to_bounds: false,
+            diagonals: true,
+            diagonal_cost: std::f64::consts::SQRT_2,
+            smooth: true,
+        }
+    }
+}
+
+// One path to find with `find_paths`
+#[derive(Deserialize)]
+pub struct PathQuery {
+    pub map: String,
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+    #[serde(default)]
+    pub options: FindPathOptions,
+}
+
+impl FindPathOptions {
+    pub(super) fn from_js(options_js: Option<JsValue>) -> Result<FindPathOptions, QueryError> {
+        match options_js {
+            None => Ok(FindPathOptions::default()),
+            Some(v) => from_js(&v),
+        }
+    }
+}
+
+// How far (in pixels) a start or goal in a wall is moved to find walkable ground. Characters
+// often stand in the margin `prepare` leaves along walls.
+const SNAP_DISTANCE: i32 = 50;
+
+// The ends of a path have to be on the map, or be moved onto it
+fn on_map(
+    grid: &Grid,
+    map_name: &str,
+    x: f32,
+    y: f32,
+    options: &FindPathOptions,
+) -> Result<(f32, f32), QueryError> {
+    if grid.contains(x, y) {
+        return Ok((x, y));
+    }
+    if options.clamp_to_bounds && !x.is_nan() && !y.is_nan() {
+        let max_x = (grid.min_x + grid.width - 1) as f32;
+        let max_y = (grid.min_y + grid.height() - 1) as f32;
+        return Ok((
+            x.clamp(grid.min_x as f32, max_x),
+            y.clamp(grid.min_y as f32, max_y),
+        ));
+    }
+    Err(QueryError::OutOfBounds {
+        map: map_name.to_string(),
+        x,
+        y,
+    })
+}
+
+// Moves an end out of a wall, to the closest walkable cell that `reachable` agrees with
+fn snap(
+    grid: &Grid,
+    walls: &[Wall],
+    x: f32,
+    y: f32,
+    reachable: impl Fn(i32, i32) -> bool,
+) -> Option<(Cell, PathPoint)> {
+    let cell = (to_grid(x), to_grid(y));
+    if grid.is_walkable(cell.0, cell.1) {
+        return Some((cell, PathPoint { x, y }));
+    }
+    // The closest walkable cell can be on the other side of the wall (e.g. when standing in the
+    // wider margin on one side of it), so only look on this side
+    let same_side = walls::same_side(walls, x, y, SNAP_DISTANCE);
+    let accept = |x, y| same_side(x, y) && reachable(x, y);
+    grid.nearest_walkable(cell.0, cell.1, SNAP_DISTANCE, accept)
+        .map(|(x, y)| {
+            let point = PathPoint {
+                x: x as f32,
+                y: y as f32,
+            };
+            ((x, y), point)
+        })
+}
+
+// Searches from the start cell (in game coordinates) to the goal, and returns the points where
+// the path turns in game coordinates
+fn search(
+    grid: &Grid,
+    start: Cell,
+    goal: Goal<'_>,
+    options: &FindPathOptions,
+) -> Option<Vec<PathPoint>> {
+    let search = Search {
+        grid,
+        height: grid.height(),
+        goal,
+        diagonals: options.diagonals,
+        diagonal_cost: match options.diagonal_cost {
+            cost if cost.is_nan() => std::f64::consts::SQRT_2,
+            cost => cost.clamp(1.0, 2.0),
+        },
+        smooth: options.smooth,
+    };
+    let cells = search.run((start.0 - grid.min_x, start.1 - grid.min_y))?;
+    Some(
+        cells
+            .iter()
+            .map(|(x, y)| PathPoint {
+                x: (x + grid.min_x) as f32,
+                y: (y + grid.min_y) as f32,
+            })
+            .collect(),
+    )
+}
+
+// For use from Rust. Returns `None` if there's no walking path between the points.
+pub fn shortest_path(
+    map_name: &str,
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+    options: &FindPathOptions,
+) -> Result<Option<Vec<PathPoint>>, QueryError> {
+    let grid = get_grid(map_name)?;
+    let map = get_map(map_name)?;
+    let (x1, y1) = on_map(&grid, map_name, x1, y1, options)?;
+    let (x2, y2) = on_map(&grid, map_name, x2, y2, options)?;
+    let (start, start_point) = match snap(&grid, &map.walls, x1, y1, |_, _| true) {
+        None => return Ok(None),
+        Some(v) => v,
+    };
+    // A goal in a wall can be next to walkable cells the start can't get to (e.g. on the far side
+    // of a thin wall), so move it to the closest one the start is connected to
+    let start_region = grid.region(start.0, start.1);
+    let reachable = |x, y| match (start_region, grid.region(x, y)) {
+        (Some(a), Some(b)) => regions_connected(a, b) != Some(false),
+        _ => false,
+    };
+    let (goal, goal_point) = match snap(&grid, &map.walls, x2, y2, reachable) {
+        None => return Ok(None),
+        Some(v) => v,
+    };
+
+    // Points that aren't connected can't have a path, no need to search. The search finds out
+    // itself for points in the shared region.
+    if !reachable(goal.0, goal.1) {
+        return Ok(None);
+    }
+
+    let (goal_x, goal_y) = (goal.0 - grid.min_x, goal.1 - grid.min_y);
+    let goal = Goal {
+        x1: goal_x,
+        y1: goal_y,
+        x2: goal_x,
+        y2: goal_y,
+        polygon: None,
+    };
+    let mut path = match search(&grid, start, goal, options) {
+        None => return Ok(None),
+        Some(v) => v,
+    };
+
+    // The ends are where the caller asked for (unless they were moved out of a wall), not the
+    // corner of their cells. Ends in the same cell still need a line from one to the other.
+    if path.len() == 1 {
+        path.push(goal_point);
+    }
+    path[0] = start_point;
+    *path.last_mut().unwrap() = goal_point;
+
+    if let Some(max_segment_length) = options.max_segment_length {
+        path = split_path(&path, max_segment_length)?;
+    }
+    Ok(Some(path))
+}
+
+// An area to find a path into, in game coordinates
+pub enum GoalArea {
+    // x1, y1, x2, y2
+    Rect(f32, f32, f32, f32),
+    Polygon(Vec<PathPoint>),
+}
+
+// For use from Rust. The shortest walking path from (x, y) into the area. The search stops at the
+// first walkable point inside it, so the path ends where it enters the area. `None` if no part of
+// the area can be walked to.
+pub fn shortest_path_to_area(
+    map_name: &str,
+    x: f32,
+    y: f32,
+    area: &GoalArea,
+    options: &FindPathOptions,
+) -> Result<Option<Vec<PathPoint>>, QueryError> {
+    let grid = get_grid(map_name)?;
+    let map = get_map(map_name)?;
+    let (x, y) = on_map(&grid, map_name, x, y, options)?;
+    let (start, start_point) = match snap(&grid, &map.walls, x, y, |_, _| true) {
+        None => return Ok(None),
+        Some(v) => v,
+    };
+
+    let (x1, y1, x2, y2) = match area {
+        GoalArea::Rect(x1, y1, x2, y2) => (x1.min(*x2), y1.min(*y2), x1.max(*x2), y1.max(*y2)),
+        GoalArea::Polygon(points) if points.len() >= 3 => points.iter().fold(
+            (
+                f32::INFINITY,
+                f32::INFINITY,
+                f32::NEG_INFINITY,
+                f32::NEG_INFINITY,
+            ),
+            |(x1, y1, x2, y2), p| (x1.min(p.x), y1.min(p.y), x2.max(p.x), y2.max(p.y)),
+        ),
+        GoalArea::Polygon(_) => return Ok(None),
+    };
+    let polygon: Option<Vec<(f64, f64)>> = match area {
+        GoalArea::Rect(..) => None,
+        GoalArea::Polygon(points) => Some(
+            points
+                .iter()
+                .map(|p| {
+                    (
+                        (p.x - grid.min_x as f32) as f64,
+                        (p.y - grid.min_y as f32) as f64,
+                    )
+                })
+                .collect(),
+        ),
+    };
+
+    // The cells in the area's bounding box, cut to the map
+    let goal = Goal {
+        x1: to_grid(x1).max(grid.min_x) - grid.min_x,
+        y1: to_grid(y1).max(grid.min_y) - grid.min_y,
+        x2: to_grid(x2).min(grid.min_x + grid.width - 1) - grid.min_x,
+        y2: to_grid(y2).min(grid.min_y + grid.height() - 1) - grid.min_y,
+        polygon: polygon.as_deref(),
+    };
+    if goal.x1 > goal.x2 || goal.y1 > goal.y2 {
+        return Ok(None);
+    }
+
+    // Without a cell in the start's region, the search would look through the whole region
+    // before giving up
+    let region = grid.data[grid.index(start.0, start.1).unwrap()];
+    let reachable = (goal.y1..=goal.y2).any(|y| {
+        let row = y * grid.width;
+        (goal.x1..=goal.x2).any(|x| grid.data[(row + x) as usize] == region && goal.contains(x, y))
+    });
+    if !reachable {
+        return Ok(None);
+    }
+
+    let mut path = match search(&grid, start, goal, options) {
+        None => return Ok(None),
+        Some(v) => v,
+    };
+    path[0] = start_point;
+
+    if let Some(max_segment_length) = options.max_segment_length {
+        path = split_path(&path, max_segment_length)?;
+    }
+    Ok(Some(path))
+}
+
+/// Returns the shortest walking path from (x1, y1) to (x2, y2) on the map as the points where
+/// it turns, or `undefined` if you can't walk between them. Ends that are in a wall are moved to
+/// the closest walkable point first. Each point's `cost` is how far it is to walk there from the
+/// start.
+#[wasm_bindgen(unchecked_return_type = "PathStep[] | undefined")]
+pub fn find_path(
+    map_name: &str,
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+    #[wasm_bindgen(unchecked_param_type = "FindPathOptions")] options_js: Option<JsValue>,
+) -> Result<JsValue, QueryError> {
+    let options = FindPathOptions::from_js(options_js)?;
+    Ok(match shortest_path(map_name, x1, y1, x2, y2, &options)? {
+        None => JsValue::UNDEFINED,
+        Some(path) => to_js(&path_steps(&path)),
+    })
+}
+
+/// Returns how far it is to walk from (x1, y1) to (x2, y2) on the map, in pixels, or `undefined`
+/// if you can't walk between them. It's the `cost` of the last point of `find_path`'s path,
+/// without passing the path to JavaScript. Both points have to be on the same map.
+#[wasm_bindgen]
+pub fn get_distance(
+    map_name: &str,
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+    #[wasm_bindgen(unchecked_param_type = "FindPathOptions")] options_js: Option<JsValue>,
+) -> Result<Option<f32>, QueryError> {
+    let options = FindPathOptions::from_js(options_js)?;
+    Ok(shortest_path(map_name, x1, y1, x2, y2, &options)?.map(|path| path_length(&path)))
+}
+
+/// Same as `find_path`, but to the closest walkable point in the rectangle from (x1, y1) to
+/// (x2, y2). The path ends where it first enters the rectangle.
+#[wasm_bindgen(unchecked_return_type = "PathStep[] | undefined")]
+#[allow(clippy::too_many_arguments)]
+pub fn find_path_to_rect(
+    map_name: &str,
+    x: f32,
+    y: f32,
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+    #[wasm_bindgen(unchecked_param_type = "FindPathOptions")] options_js: Option<JsValue>,
+) -> Result<JsValue, QueryError> {
+    let options = FindPathOptions::from_js(options_js)?;
+    let area = GoalArea::Rect(x1, y1, x2, y2);
+    Ok(
+        match shortest_path_to_area(map_name, x, y, &area, &options)? {
+            None => JsValue::UNDEFINED,
+            Some(path) => to_js(&path_steps(&path)),
+        },
+    )
+}
+
+/// Same as `find_path_to_rect`, but into a polygon (e.g. an event area). The last point connects
+/// back to the first.
+#[wasm_bindgen(unchecked_return_type = "PathStep[] | undefined")]
+pub fn find_path_to_polygon(
+    map_name: &str,
+    x: f32,
+    y: f32,
+    #[wasm_bindgen(unchecked_param_type = "Point[]")] polygon_js: &JsValue,
+    #[wasm_bindgen(unchecked_param_type = "FindPathOptions")] options_js: Option<JsValue>,
+) -> Result<JsValue, QueryError> {
+    let polygon: Vec<PathPoint> = from_js(polygon_js)?;
+    let options = FindPathOptions::from_js(options_js)?;
+    let area = GoalArea::Polygon(polygon);
+    Ok(
+        match shortest_path_to_area(map_name, x, y, &area, &options)? {
+            None => JsValue::UNDEFINED,
+            Some(path) => to_js(&path_steps(&path)),
+        },
+    )
+}
+
+/// Same as `find_path`, but returns the points as a flat `[x1, y1, x2, y2, ...]` array, which
+/// is much cheaper to pass back to JavaScript than objects
+#[wasm_bindgen]
+pub fn find_path_f32(
+    map_name: &str,
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+    #[wasm_bindgen(unchecked_param_type = "FindPathOptions")] options_js: Option<JsValue>,
+) -> Result<Option<Vec<f32>>, QueryError> {
+    let options = FindPathOptions::from_js(options_js)?;
+    Ok(shortest_path(map_name, x1, y1, x2, y2, &options)?
+        .map(|path| path.iter().flat_map(|point| [point.x, point.y]).collect()))
+}
+
+/// Same as `find_path_f32`, but writes the points into `buffer` (a `Float32Array`, which can be
+/// a view of a `SharedArrayBuffer`) as `[x1, y1, x2, y2, ...]` instead of making a new array.
+/// Returns how many points the path has, or `undefined` if there's no path. Points that don't
+/// fit aren't written, so a result of more than `buffer.length / 2` means the buffer was too
+/// small.
+#[wasm_bindgen]
+pub fn find_path_into(
+    map_name: &str,
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+    buffer: &mut [f32],
+    #[wasm_bindgen(unchecked_param_type = "FindPathOptions")] options_js: Option<JsValue>,
+) -> Result<Option<u32>, QueryError> {
+    let options = FindPathOptions::from_js(options_js)?;
+    let path = match shortest_path(map_name, x1, y1, x2, y2, &options)? {
+        None => return Ok(None),
+        Some(v) => v,
+    };
+    for (point, slot) in path.iter().zip(buffer.chunks_exact_mut(2)) {
+        slot[0] = point.x;
+        slot[1] = point.y;
+    }
+    Ok(Some(path.len() as u32))
+}
+
+// For use from Rust. The shortest path for each query, like `shortest_path`
+pub fn shortest_paths(queries: &[PathQuery]) -> Result<Vec<Option<Vec<PathPoint>>>, QueryError> {
+    queries
+        .iter()
+        .map(|q| shortest_path(&q.map, q.x1, q.y1, q.x2, q.y2, &q.options))
+        .collect()
+}
+
+/// Finds many paths in one call, e.g. for every character in a party, which saves crossing into
+/// WebAssembly for each one. Takes `{map, x1, y1, x2, y2, options}` queries and returns what
+/// `find_path` would for each, with `null` where there's no path. Throws if any query does.
+#[wasm_bindgen(unchecked_return_type = "(PathStep[] | null)[]")]
+pub fn find_paths(
+    #[wasm_bindgen(unchecked_param_type = "PathQuery[]")] queries_js: &JsValue,
+) -> Result<JsValue, QueryError> {
+    let queries: Vec<PathQuery> = from_js(queries_js)?;
+    let paths: Vec<Option<Vec<PathStep>>> = shortest_paths(&queries)?
+        .iter()
+        .map(|path| path.as_deref().map(path_steps))
+        .collect();
+    Ok(to_js(&paths))
+}
+
+// For use from Rust. Moves the leader's path `offset` pixels to the right of the direction of
+// travel (to the left if negative), keeping to the leader's path where that's in a wall.
+pub fn follow_path(
+    map_name: &str,
+    leader_path: &[PathPoint],
+    offset: f32,
+) -> Result<Vec<PathPoint>, QueryError> {
+    let grid = get_grid(map_name)?;
+    let can_walk = |a: PathPoint, b: PathPoint| {
+        grid.can_walk_path(to_grid(a.x), to_grid(a.y), to_grid(b.x), to_grid(b.y))
+    };
+
+    // Every point on the path can walk straight to the leader's point it comes from, so the
+    // follower can always get back on the leader's path
+    let mut path: Vec<PathPoint> = Vec::new();
+    for (i, point) in leader_path.iter().enumerate() {
+        let before = leader_path[i.saturating_sub(1)];
+        let after = leader_path[(i + 1).min(leader_path.len() - 1)];
+        let (dx, dy) = (after.x - before.x, after.y - before.y);
+        let length = dx.hypot(dy);
+        let moved = if length == 0.0 {
+            *point
+        } else {
+            PathPoint {
+                x: point.x - dy / length * offset,
+                y: point.y + dx / length * offset,
+            }
+        };
+        let next = if can_walk(*point, moved) {
+            moved
+        } else {
+            *point
+        };
+
+        if let Some(last) = path.last().copied() {
+            if !can_walk(last, next) {
+                // Go back to the leader's path and follow it to this point
+                let previous = leader_path[i - 1];
+                if last != previous {
+                    path.push(previous);
+                }
+                if next != *point {
+                    path.push(*point);
+                }
+            }
+        }
+        if path.last() != Some(&next) {
+            path.push(next);
+        }
+    }
+    Ok(path)
+}
+
+/// Returns a path for a character following a leader along `leader_path` (as returned by
+/// `find_path`), `offset` pixels to the right of it (to the left if negative). Where that would
+/// be in a wall, the path keeps to the leader's path instead.
+#[wasm_bindgen(unchecked_return_type = "Point[]")]
+pub fn derive_follow_path(
+    map_name: &str,
+    #[wasm_bindgen(unchecked_param_type = "Point[]")] leader_path_js: &JsValue,
+    offset: f32,
+) -> Result<JsValue, QueryError> {
+    let leader_path: Vec<PathPoint> = from_js(leader_path_js)?;
+
+    Ok(to_js(&follow_path(map_name, &leader_path, offset)?))
+}
+
+#[cfg(all(test, feature = "prepare"))]
+mod tests {
+    use super::super::prepare_from_json;
+    use super::super::testing::{random_map, walkable_point, Rng};
+    use super::*;
+    use std::cmp::Reverse;
+
+    // The length of the shortest path between the cells (in game coordinates) with moves in the
+    // grid directions, by trying every cell
+    fn dijkstra(
+        grid: &Grid,
+        start: Cell,
+        goal: Cell,
+        diagonals: bool,
+        diagonal_cost: f64,
+    ) -> Option<f64> {
+        let index = |(x, y): Cell| grid.index(x, y).unwrap();
+        let mut costs = vec![f64::INFINITY; grid.data.len()];
+        let mut queue = BinaryHeap::new();
+        costs[index(start)] = 0.0;
+        queue.push(Reverse((0.0f64.to_bits(), start)));
+        while let Some(Reverse((cost, (x, y)))) = queue.pop() {
+            let cost = f64::from_bits(cost);
+            if (x, y) == goal {
+                return Some(cost);
+            }
+            if cost > costs[index((x, y))] {
+                continue;
+            }
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    let diagonal = dx != 0 && dy != 0;
+                    if (dx, dy) == (0, 0) || !grid.is_walkable(x + dx, y + dy) {
+                        continue;
+                    }
+                    // Diagonal moves can't cut the corner of a wall
+                    if diagonal
+                        && (!diagonals
+                            || !grid.is_walkable(x + dx, y)
+                            || !grid.is_walkable(x, y + dy))
+                    {
+                        continue;
+                    }
+                    let next = (x + dx, y + dy);
+                    let next_cost = cost + if diagonal { diagonal_cost } else { 1.0 };
+                    if next_cost < costs[index(next)] {
+                        costs[index(next)] = next_cost;
+                        queue.push(Reverse((next_cost.to_bits(), next)));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn grid_length(path: &[PathPoint], diagonal_cost: f64) -> f64 {
+        path.windows(2)
+            .map(|s| {
+                let dx = (s[1].x - s[0].x).abs() as f64;
+                let dy = (s[1].y - s[0].y).abs() as f64;
+                dx.max(dy) - dx.min(dy) + dx.min(dy) * diagonal_cost
+            })
+            .sum()
+    }
+
+    #[test]
+    fn grid_paths_are_as_short_as_dijkstra() {
+        // Costs outside of [1, 2] are clamped
+        let settings = [
+            (false, 1.0, 1.0),
+            (true, std::f64::consts::SQRT_2, std::f64::consts::SQRT_2),
+            (true, 1.0, 1.0),
+            (true, 1.7, 1.7),
+            (true, 2.0, 2.0),
+            (true, 0.5, 1.0),
+            (true, 3.0, 2.0),
+        ];
+        for seed in 0..6 {
+            let map_name = format!("test_grid_paths_{}", seed);
+            let mut rng = Rng::new(seed);
+            let grid = random_map(&map_name, &mut rng);
+            for _ in 0..8 {
+                let (Some(a), Some(b)) = (
+                    walkable_point(&grid, &mut rng),
+                    walkable_point(&grid, &mut rng),
+                ) else {
+                    continue;
+                };
+                for (diagonals, diagonal_cost, clamped) in settings {
+                    let options = FindPathOptions {
+                        diagonals,
+                        diagonal_cost,
+                        smooth: false,
+                        ..FindPathOptions::default()
+                    };
+                    let (x1, y1, x2, y2) = (a.0 as f32, a.1 as f32, b.0 as f32, b.1 as f32);
+                    let path = shortest_path(&map_name, x1, y1, x2, y2, &options).unwrap();
+                    let expected = dijkstra(&grid, a, b, diagonals, clamped);
+                    let case = format!("{} {:?} -> {:?} {:?}", map_name, a, b, options.diagonals);
+                    match (path, expected) {
+                        (None, None) => {}
+                        (Some(path), Some(expected)) => {
+                            let length = grid_length(&path, clamped);
+                            assert!(
+                                (length - expected).abs() < 1e-6,
+                                "{}: {} != {}",
+                                case,
+                                length,
+                                expected
+                            );
+                            for s in path.windows(2) {
+                                let (x1, y1) = (s[0].x as i32, s[0].y as i32);
+                                let (x2, y2) = (s[1].x as i32, s[1].y as i32);
+                                assert!(grid.can_walk_path(x1, y1, x2, y2), "{}", case);
+                            }
+                        }
+                        (path, expected) => panic!(
+                            "{}: {:?} but expected {:?}",
+                            case,
+                            path.map(|p| p.len()),
+                            expected
+                        ),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_paths_are_walkable_and_no_longer() {
+        for seed in 0..6 {
+            let map_name = format!("test_smooth_paths_{}", seed);
+            let mut rng = Rng::new(100 + seed);
+            let grid = random_map(&map_name, &mut rng);
+            for _ in 0..8 {
+                let (Some(a), Some(b)) = (
+                    walkable_point(&grid, &mut rng),
+                    walkable_point(&grid, &mut rng),
+                ) else {
+                    continue;
+                };
+                let (x1, y1, x2, y2) = (a.0 as f32, a.1 as f32, b.0 as f32, b.1 as f32);
+                let options = FindPathOptions::default();
+                let path = shortest_path(&map_name, x1, y1, x2, y2, &options).unwrap();
+                let expected = dijkstra(&grid, a, b, true, std::f64::consts::SQRT_2);
+                let case = format!("{} {:?} -> {:?}", map_name, a, b);
+                match (path, expected) {
+                    (None, None) => {}
+                    (Some(path), Some(expected)) => {
+                        assert!(path_length(&path) as f64 <= expected + 1e-3, "{}", case);
+                        for s in path.windows(2) {
+                            let (x1, y1) = (to_grid(s[0].x), to_grid(s[0].y));
+                            let (x2, y2) = (to_grid(s[1].x), to_grid(s[1].y));
+                            assert!(grid.can_walk_path(x1, y1, x2, y2), "{}", case);
+                        }
+                    }
+                    (path, expected) => panic!(
+                        "{}: {:?} but expected {:?}",
+                        case,
+                        path.map(|p| p.len()),
+                        expected
+                    ),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ends_are_not_snapped_through_walls() {
+        let g = r#"{"geometry":{"test_snapping":{"min_x":-200,"max_x":500,"min_y":-200,"max_y":400,"y_lines":[[100,0,300]]}},"maps":{"test_snapping":{"name":"test_snapping","spawns":[[0,0]]}}}"#;
+        prepare_from_json(g, None).unwrap();
+
+        // (100, 101) is in the wall's margin, which is closer to walkable ground above the wall
+        let options = FindPathOptions::default();
+        let path = shortest_path("test_snapping", 100.0, 101.0, 100.0, 150.0, &options)
+            .unwrap()
+            .unwrap();
+        assert!(path[0].y > 100.0);
+        assert!(path_length(&path) < 50.0);
+    }
+
+    #[test]
+    fn ends_in_the_same_cell_are_both_on_the_path() {
+        let g = r#"{"geometry":{"test_same_cell":{"min_x":-50,"max_x":50,"min_y":-50,"max_y":50}},"maps":{"test_same_cell":{"name":"test_same_cell","spawns":[[0,0]]}}}"#;
+        prepare_from_json(g, None).unwrap();
+
+        let options = FindPathOptions::default();
+        let path = shortest_path("test_same_cell", 1.2, 1.3, 1.8, 1.6, &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            path,
+            [PathPoint { x: 1.2, y: 1.3 }, PathPoint { x: 1.8, y: 1.6 }]
+        );
+    }
+
+    #[test]
+    fn batched_paths_are_the_same_as_single_ones() {
+        let mut rng = Rng::new(500);
+        let grid = random_map("test_batched_paths", &mut rng);
+        let mut queries: Vec<PathQuery> = (0..10)
+            .filter_map(|_| {
+                let a = walkable_point(&grid, &mut rng)?;
+                let b = walkable_point(&grid, &mut rng)?;
+                Some(PathQuery {
+                    map: "test_batched_paths".to_string(),
+                    x1: a.0 as f32,
+                    y1: a.1 as f32,
+                    x2: b.0 as f32,
+                    y2: b.1 as f32,
+                    options: FindPathOptions::default(),
+                })
+            })
+            .collect();
+        queries[0].options.smooth = false;
+
+        let paths = shortest_paths(&queries).unwrap();
+        assert_eq!(paths.len(), queries.len());
+        for (q, path) in queries.iter().zip(paths) {
+            assert_eq!(
+                path,
+                shortest_path(&q.map, q.x1, q.y1, q.x2, q.y2, &q.options).unwrap()
+            );
+        }
+
+        queries[5].map = "test_batched_paths_missing".to_string();
+        assert!(matches!(
+            shortest_paths(&queries),
+            Err(QueryError::NotPrepared(_))
+        ));
+    }
+
+    #[test]
+    fn distances_are_the_cost_of_the_path() {
+        let mut rng = Rng::new(600);
+        let grid = random_map("test_distances", &mut rng);
+        for _ in 0..10 {
+            let (Some(a), Some(b)) = (
+                walkable_point(&grid, &mut rng),
+                walkable_point(&grid, &mut rng),
+            ) else {
+                continue;
+            };
+            let (x1, y1, x2, y2) = (a.0 as f32, a.1 as f32, b.0 as f32, b.1 as f32);
+            let path = shortest_path(
+                "test_distances",
+                x1,
+                y1,
+                x2,
+                y2,
+                &FindPathOptions::default(),
+            )
+            .unwrap();
+            assert_eq!(
+                get_distance("test_distances", x1, y1, x2, y2, None).unwrap(),
+                path.map(|path| path_steps(&path).last().unwrap().cost)
+            );
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use super::{get_grid, prepare_from_json, Grid};
+use std::sync::Arc;
+
+// Helpers for the tests. Maps are kept in global state shared by tests running at the same time,
+// so every test prepares its own maps under its own names.
+
+// A small deterministic random number generator (xorshift), so failures can be reproduced
+pub(super) struct Rng(u64);
+
+impl Rng {
+    pub(super) fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9e3779b97f4a7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // In [from, to)
+    pub(super) fn range(&mut self, from: i32, to: i32) -> i32 {
+        from + (self.next() % (to - from) as u64) as i32
+    }
+}
+
+pub(super) const MIN_X: i32 = -64;
+pub(super) const MAX_X: i32 = 96;
+pub(super) const MIN_Y: i32 = -32;
+pub(super) const MAX_Y: i32 = 96;
+
+// Prepares a map with random walls and spawns, and returns its grid
+pub(super) fn random_map(map_name: &str, rng: &mut Rng) -> Arc<Grid> {
+    let mut line = |from: i32, to: i32, other_from: i32, other_to: i32| {
+        let at = rng.range(from, to);
+        let start = rng.range(other_from, other_to);
+        let length = rng.range(8, 80);
+        format!("[{},{},{}]", at, start, start + length)
+    };
+    let x_lines: Vec<String> = (0..8)
+        .map(|_| line(MIN_X, MAX_X, MIN_Y - 20, MAX_Y))
+        .collect();
+    let y_lines: Vec<String> = (0..8)
+        .map(|_| line(MIN_Y, MAX_Y, MIN_X - 20, MAX_X))
+        .collect();
+    let spawns: Vec<String> = (0..6)
+        .map(|_| format!("[{},{}]", rng.range(MIN_X, MAX_X), rng.range(MIN_Y, MAX_Y)))
+        .collect();
+
+    let g = format!(
+        r#"{{"geometry":{{"{map}":{{"min_x":{min_x},"max_x":{max_x},"min_y":{min_y},"max_y":{max_y},"x_lines":[{x}],"y_lines":[{y}]}}}},"maps":{{"{map}":{{"name":"{map}","spawns":[{spawns}]}}}}}}"#,
+        map = map_name,
+        min_x = MIN_X,
+        max_x = MAX_X,
+        min_y = MIN_Y,
+        max_y = MAX_Y,
+        x = x_lines.join(","),
+        y = y_lines.join(","),
+        spawns = spawns.join(","),
+    );
+    prepare_from_json(&g, None).unwrap();
+    get_grid(map_name).unwrap()
+}
+
+// A random walkable point on the grid, in game coordinates
+pub(super) fn walkable_point(grid: &Grid, rng: &mut Rng) -> Option<(i32, i32)> {
+    (0..1000)
+        .map(|_| (rng.range(MIN_X, MAX_X), rng.range(MIN_Y, MAX_Y)))
+        .find(|(x, y)| grid.is_walkable(*x, *y))
+}
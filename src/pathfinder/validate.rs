@@ -0,0 +1,216 @@
+use super::prepare::{get_walls, grid_size};
+use super::{from_js, to_grid, to_js, QueryError};
+use crate::g::*;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_VALIDATION_ISSUE: &'static str = r#"
+export interface ValidationIssue {
+    map: string;
+    kind: string;
+    message: string;
+}
+"#;
+
+#[derive(Serialize, Debug)]
+pub struct ValidationIssue {
+    pub map: String,
+    pub kind: &'static str,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn new(map: &str, kind: &'static str, message: String) -> ValidationIssue {
+        ValidationIssue {
+            map: map.to_string(),
+            kind,
+            message,
+        }
+    }
+}
+
+fn is_in_wall(geometry: &GGeometry, x: f32, y: f32) -> bool {
+    let x = to_grid(x);
+    let y = to_grid(y);
+
+    // Same areas that `prepare_map` marks as not walkable
+    get_walls(geometry).iter().any(|wall| {
+        let (x1, y1, x2, y2) = wall.blocked_area();
+        x >= x1 && x < x2 && y >= y1 && y < y2
+    })
+}
+
+pub fn validate(g: &GData) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+
+    let mut map_names: Vec<&String> = g.maps.keys().collect();
+    map_names.sort();
+
+    for map_name in map_names {
+        let map = &g.maps[map_name];
+        if map.ignore.is_some() {
+            continue;
+        }
+
+        // Geometry
+        match g.geometry.get(map_name) {
+            None => issues.push(ValidationIssue::new(
+                map_name,
+                "missing_geometry",
+                "map has no entry in G.geometry".to_string(),
+            )),
+            Some(geometry) if grid_size(geometry).is_none() => issues.push(ValidationIssue::new(
+                map_name,
+                "invalid_bounds",
+                format!(
+                    "bounds x {}..{}, y {}..{} are empty or too big",
+                    geometry.min_x, geometry.max_x, geometry.min_y, geometry.max_y
+                ),
+            )),
+            Some(geometry) => {
+                for (i, spawn) in map.spawns.iter().enumerate() {
+                    let (x, y) = (spawn[0], spawn[1]);
+                    if x < geometry.min_x as f32
+                        || x >= geometry.max_x as f32
+                        || y < geometry.min_y as f32
+                        || y >= geometry.max_y as f32
+                    {
+                        issues.push(ValidationIssue::new(
+                            map_name,
+                            "spawn_out_of_bounds",
+                            format!("spawn {} at ({}, {}) is outside the map bounds", i, x, y),
+                        ));
+                    } else if is_in_wall(geometry, x, y) {
+                        issues.push(ValidationIssue::new(
+                            map_name,
+                            "spawn_in_wall",
+                            format!("spawn {} at ({}, {}) is inside a wall", i, x, y),
+                        ));
+                    }
+                }
+            }
+        }
+
+        // Entries we couldn't parse
+        if let Some(geometry) = g.geometry.get(map_name) {
+            let lines = geometry
+                .x_lines
+                .skipped
+                .iter()
+                .map(|s| ("x_line", s))
+                .chain(geometry.y_lines.skipped.iter().map(|s| ("y_line", s)));
+            for (kind, (i, message)) in lines {
+                issues.push(ValidationIssue::new(
+                    map_name,
+                    "malformed_line",
+                    format!("{} {} was skipped: {}", kind, i, message),
+                ));
+            }
+        }
+        for (i, message) in &map.spawns.skipped {
+            issues.push(ValidationIssue::new(
+                map_name,
+                "malformed_spawn",
+                format!("spawn {} was skipped: {}", i, message),
+            ));
+        }
+        for (i, message) in &map.doors.skipped {
+            issues.push(ValidationIssue::new(
+                map_name,
+                "malformed_door",
+                format!("door {} was skipped: {}", i, message),
+            ));
+        }
+
+        // Doors
+        for (i, door) in map.doors.iter().enumerate() {
+            match g.maps.get(&door.map) {
+                None => issues.push(ValidationIssue::new(
+                    map_name,
+                    "unknown_door_map",
+                    format!("door {} leads to unknown map '{}'", i, door.map),
+                )),
+                Some(to_map) => {
+                    if door.spawn >= to_map.spawns.len() {
+                        issues.push(ValidationIssue::new(
+                            map_name,
+                            "door_spawn_out_of_range",
+                            format!(
+                                "door {} leads to spawn {} on '{}', which only has {} spawns",
+                                i,
+                                door.spawn,
+                                door.map,
+                                to_map.spawns.len()
+                            ),
+                        ));
+                    }
+                }
+            }
+            if let Some(from_spawn) = door.from_spawn {
+                if from_spawn >= map.spawns.len() {
+                    issues.push(ValidationIssue::new(
+                        map_name,
+                        "door_spawn_out_of_range",
+                        format!(
+                            "door {} is entered from spawn {}, but the map only has {} spawns",
+                            i,
+                            from_spawn,
+                            map.spawns.len()
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    // Transporter (and other teleporting NPC) places
+    let mut npc_ids: Vec<&String> = g.npcs.keys().collect();
+    npc_ids.sort();
+    for npc_id in npc_ids {
+        let places = match &g.npcs[npc_id].places {
+            None => continue,
+            Some(v) => v,
+        };
+        let mut place_names: Vec<&String> = places.keys().collect();
+        place_names.sort();
+        for place in place_names {
+            let spawn = places[place];
+            match g.maps.get(place) {
+                None => issues.push(ValidationIssue::new(
+                    place,
+                    "unknown_transport_map",
+                    format!("npc '{}' transports to unknown map '{}'", npc_id, place),
+                )),
+                Some(map) if map.ignore.is_some() => issues.push(ValidationIssue::new(
+                    place,
+                    "transport_to_ignored_map",
+                    format!("npc '{}' transports to ignored map '{}'", npc_id, place),
+                )),
+                Some(map) if spawn >= map.spawns.len() => issues.push(ValidationIssue::new(
+                    place,
+                    "transport_spawn_out_of_range",
+                    format!(
+                        "npc '{}' transports to spawn {} on '{}', which only has {} spawns",
+                        npc_id,
+                        spawn,
+                        place,
+                        map.spawns.len()
+                    ),
+                )),
+                Some(_) => {}
+            }
+        }
+    }
+
+    issues
+}
+
+/// Checks 'G' for data that would make `prepare` panic or produce a broken graph, and returns
+/// a list of `{map, kind, message}` issues. An empty list means no problems were found.
+#[wasm_bindgen(unchecked_return_type = "ValidationIssue[]")]
+pub fn validate_g(g_js: &JsValue) -> Result<JsValue, QueryError> {
+    let g: GData = from_js(g_js)?;
+
+    Ok(to_js(&validate(&g)))
+}
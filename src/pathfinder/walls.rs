@@ -0,0 +1,147 @@
+use super::{get_map, to_js, QueryError, Wall};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_NEAREST_WALL: &'static str = r#"
+export interface NearestWall {
+    distance: number;
+    x: number;
+    y: number;
+    direction_x: number;
+    direction_y: number;
+}
+"#;
+
+// The closest point on any wall to a point, and which way it is
+#[derive(Serialize, Debug)]
+pub struct NearestWall {
+    pub distance: f32,
+    // The closest point on the wall
+    pub x: f32,
+    pub y: f32,
+    // The unit vector pointing at the wall, (0, 0) when the point is on it
+    pub direction_x: f32,
+    pub direction_y: f32,
+}
+
+// Queries on the walls themselves rather than the grid, for things that don't care about the
+// margin characters keep from walls (e.g. projectiles)
+
+// Positive if c is clockwise of the line from a to b (with y pointing down), 0 if it's on it
+fn cross(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+impl Wall {
+    // Whether the line from a to b touches the wall
+    fn crosses(&self, a: (f64, f64), b: (f64, f64)) -> bool {
+        let (c, d) = (
+            (self.x1 as f64, self.y1 as f64),
+            (self.x2 as f64, self.y2 as f64),
+        );
+        a.0.min(b.0) <= c.0.max(d.0)
+            && c.0.min(d.0) <= a.0.max(b.0)
+            && a.1.min(b.1) <= c.1.max(d.1)
+            && c.1.min(d.1) <= a.1.max(b.1)
+            && cross(a, b, c) * cross(a, b, d) <= 0.0
+            && cross(c, d, a) * cross(c, d, b) <= 0.0
+    }
+
+    // The closest point on the wall. Walls are only ever vertical or horizontal.
+    fn closest_point(&self, x: f32, y: f32) -> (f32, f32) {
+        let clamp = |v: f32, a: i32, b: i32| v.clamp(a.min(b) as f32, a.max(b) as f32);
+        (clamp(x, self.x1, self.x2), clamp(y, self.y1, self.y2))
+    }
+}
+
+// For moving (x, y) to a point at most `distance` away, e.g. out of a wall's margin. Tells whether
+// the straight line to (to_x, to_y) stays on this side of the walls, so the point isn't moved
+// through a wall to the other side of it. Walls the point is on don't count.
+pub(super) fn same_side(
+    walls: &[Wall],
+    x: f32,
+    y: f32,
+    distance: i32,
+) -> impl Fn(i32, i32) -> bool + '_ {
+    let a = (x as f64, y as f64);
+    let near: Vec<&Wall> = walls
+        .iter()
+        .filter(|wall| {
+            let (wall_x, wall_y) = wall.closest_point(x, y);
+            (wall_x - x).hypot(wall_y - y) <= distance as f32 && !wall.crosses(a, a)
+        })
+        .collect();
+    move |to_x, to_y| {
+        let b = (to_x as f64, to_y as f64);
+        !near.iter().any(|wall| wall.crosses(a, b))
+    }
+}
+
+/// Returns the map's walls as `[x1, y1, x2, y2]` lines in game coordinates, as they are in 'G'
+/// (without the margin characters keep from them)
+#[wasm_bindgen(unchecked_return_type = "[number, number, number, number][]")]
+pub fn get_walls(map_name: &str) -> Result<JsValue, QueryError> {
+    let map = get_map(map_name)?;
+    let walls: Vec<[i32; 4]> = map
+        .walls
+        .iter()
+        .map(|wall| [wall.x1, wall.y1, wall.x2, wall.y2])
+        .collect();
+
+    Ok(to_js(&walls))
+}
+
+/// Returns `true` if nothing blocks a straight line from (x1, y1) to (x2, y2), like a
+/// projectile's. Unlike `can_walk_path`, only the walls themselves block it, not the margin
+/// characters have to keep from them.
+#[wasm_bindgen]
+pub fn has_line_of_sight(
+    map_name: &str,
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+) -> Result<bool, QueryError> {
+    let map = get_map(map_name)?;
+    let (a, b) = ((x1 as f64, y1 as f64), (x2 as f64, y2 as f64));
+    Ok(!map.walls.iter().any(|wall| wall.crosses(a, b)))
+}
+
+// For use from Rust. Returns `None` if the map has no walls.
+pub fn closest_wall(map_name: &str, x: f32, y: f32) -> Result<Option<NearestWall>, QueryError> {
+    let map = get_map(map_name)?;
+    let closest = map
+        .walls
+        .iter()
+        .map(|wall| {
+            let (wall_x, wall_y) = wall.closest_point(x, y);
+            ((wall_x - x).hypot(wall_y - y), wall_x, wall_y)
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(closest.map(|(distance, wall_x, wall_y)| {
+        let (direction_x, direction_y) = if distance == 0.0 {
+            (0.0, 0.0)
+        } else {
+            ((wall_x - x) / distance, (wall_y - y) / distance)
+        };
+        NearestWall {
+            distance,
+            x: wall_x,
+            y: wall_y,
+            direction_x,
+            direction_y,
+        }
+    }))
+}
+
+/// Returns the closest point on any wall to (x, y) as `{distance, x, y, direction_x,
+/// direction_y}`, where the direction is the unit vector pointing at the wall, or `undefined` if
+/// the map has no walls. Handy for not backing into walls while kiting.
+#[wasm_bindgen(unchecked_return_type = "NearestWall | undefined")]
+pub fn nearest_wall(map_name: &str, x: f32, y: f32) -> Result<JsValue, QueryError> {
+    Ok(match closest_wall(map_name, x, y)? {
+        None => JsValue::UNDEFINED,
+        Some(wall) => to_js(&wall),
+    })
+}
@@ -0,0 +1,510 @@
+use super::{
+    Destination, Door, Grid, MapInfo, MonsterArea, NpcLocation, QueryError, Transporter, Wall,
+    GRIDS, MAPS,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+
+// A "world" is every prepared map in a compact binary form, so the work `prepare` does can be
+// done ahead of time (see `src/bin/precompute.rs`) and loaded with `load_world`.
+//
+// Layout (little endian): the magic bytes, the format version, then for each map its name,
+// its grid (with the cells run-length encoded) and its info.
+const MAGIC: &[u8; 4] = b"ALPW";
+const FORMAT_VERSION: u32 = 5;
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.bytes.push(v);
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn i32(&mut self, v: i32) {
+        self.bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn f32(&mut self, v: f32) {
+        self.bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn len(&mut self, v: usize) {
+        self.u32(v as u32);
+    }
+
+    fn string(&mut self, v: &str) {
+        self.len(v.len());
+        self.bytes.extend_from_slice(v.as_bytes());
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = match self.position.checked_add(n) {
+            Some(end) if end <= self.bytes.len() => end,
+            _ => return Err(format!("unexpected end of world at byte {}", self.position)),
+        };
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn len(&mut self) -> Result<usize, String> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.len()?;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|e| e.to_string())
+    }
+}
+
+fn write_grid(w: &mut Writer, grid: &Grid) {
+    w.i32(grid.width);
+    w.i32(grid.min_x);
+    w.i32(grid.min_y);
+    w.len(grid.data.len());
+
+    // Grids are mostly long runs of the same value
+    let mut runs: Vec<(u8, u32)> = Vec::new();
+    for cell in &grid.data {
+        match runs.last_mut() {
+            Some((value, length)) if value == cell => *length += 1,
+            _ => runs.push((*cell, 1)),
+        }
+    }
+    w.len(runs.len());
+    for (value, length) in runs {
+        w.u8(value);
+        w.u32(length);
+    }
+}
+
+fn read_grid(r: &mut Reader) -> Result<Grid, String> {
+    let width = r.i32()?;
+    let min_x = r.i32()?;
+    let min_y = r.i32()?;
+    let size = r.len()?;
+
+    // Don't trust the sizes in the world before allocating for them. Like `build_grid`, the
+    // cells have to be indexable with i32s, and the runs can't add up to more than the grid.
+    if width <= 0 || size % width as usize != 0 || size > i32::MAX as usize {
+        return Err("grid size doesn't match its width".to_string());
+    }
+
+    let mut data = Vec::new();
+    for _ in 0..r.len()? {
+        let value = r.u8()?;
+        let length = r.u32()? as usize;
+        if length > size - data.len() {
+            return Err("grid has more data than its size".to_string());
+        }
+        data.resize(data.len() + length, value);
+    }
+    if data.len() != size {
+        return Err("grid size doesn't match its data".to_string());
+    }
+
+    let mut grid = Grid {
+        width,
+        min_x,
+        min_y,
+        data,
+        tiles: Vec::new(),
+    };
+    grid.summarize();
+    Ok(grid)
+}
+
+fn write_info(w: &mut Writer, info: &MapInfo) {
+    w.u64(info.hash);
+    w.i32(info.min_x);
+    w.i32(info.max_x);
+    w.i32(info.min_y);
+    w.i32(info.max_y);
+
+    w.len(info.spawns.len());
+    for spawn in &info.spawns {
+        w.len(spawn.len());
+        spawn.iter().for_each(|v| w.f32(*v));
+    }
+
+    w.len(info.doors.len());
+    for door in &info.doors {
+        match door {
+            None => w.u8(0),
+            Some(door) => {
+                w.u8(1);
+                w.f32(door.x);
+                w.f32(door.y);
+                w.f32(door.width);
+                w.f32(door.height);
+                w.string(&door.map);
+                w.len(door.spawn);
+                match &door.kind {
+                    None => w.u8(0),
+                    Some(kind) => {
+                        w.u8(1);
+                        w.string(kind);
+                    }
+                }
+            }
+        }
+    }
+
+    w.len(info.transporters.len());
+    for transporter in &info.transporters {
+        w.string(&transporter.id);
+        w.f32(transporter.x);
+        w.f32(transporter.y);
+        w.len(transporter.destinations.len());
+        for destination in &transporter.destinations {
+            w.string(&destination.map);
+            w.len(destination.spawn);
+        }
+    }
+
+    w.len(info.npcs.len());
+    for npc in &info.npcs {
+        w.string(&npc.id);
+        match &npc.name {
+            None => w.u8(0),
+            Some(name) => {
+                w.u8(1);
+                w.string(name);
+            }
+        }
+        w.string(&npc.map);
+        w.f32(npc.x);
+        w.f32(npc.y);
+    }
+
+    w.len(info.walls.len());
+    for wall in &info.walls {
+        w.i32(wall.x1);
+        w.i32(wall.y1);
+        w.i32(wall.x2);
+        w.i32(wall.y2);
+    }
+
+    w.len(info.monsters.len());
+    for area in &info.monsters {
+        w.string(&area.kind);
+        w.f32(area.x1);
+        w.f32(area.y1);
+        w.f32(area.x2);
+        w.f32(area.y2);
+    }
+}
+
+fn read_info(r: &mut Reader) -> Result<MapInfo, String> {
+    let hash = r.u64()?;
+    let min_x = r.i32()?;
+    let max_x = r.i32()?;
+    let min_y = r.i32()?;
+    let max_y = r.i32()?;
+
+    let mut spawns = Vec::new();
+    for _ in 0..r.len()? {
+        let mut spawn = Vec::new();
+        for _ in 0..r.len()? {
+            spawn.push(r.f32()?);
+        }
+        // Spawns are looked up by their x and y
+        if spawn.len() < 2 {
+            return Err("spawn has fewer than 2 values".to_string());
+        }
+        spawns.push(spawn);
+    }
+
+    let mut doors = Vec::new();
+    for _ in 0..r.len()? {
+        doors.push(match r.u8()? {
+            0 => None,
+            _ => Some(Door {
+                x: r.f32()?,
+                y: r.f32()?,
+                width: r.f32()?,
+                height: r.f32()?,
+                map: r.string()?,
+                spawn: r.len()?,
+                kind: match r.u8()? {
+                    0 => None,
+                    _ => Some(r.string()?),
+                },
+            }),
+        });
+    }
+
+    let mut transporters = Vec::new();
+    for _ in 0..r.len()? {
+        let id = r.string()?;
+        let x = r.f32()?;
+        let y = r.f32()?;
+        let mut destinations = Vec::new();
+        for _ in 0..r.len()? {
+            destinations.push(Destination {
+                map: r.string()?,
+                spawn: r.len()?,
+            });
+        }
+        transporters.push(Transporter {
+            id,
+            x,
+            y,
+            destinations,
+        });
+    }
+
+    let mut npcs = Vec::new();
+    for _ in 0..r.len()? {
+        npcs.push(NpcLocation {
+            id: r.string()?,
+            name: match r.u8()? {
+                0 => None,
+                _ => Some(r.string()?),
+            },
+            map: r.string()?,
+            x: r.f32()?,
+            y: r.f32()?,
+        });
+    }
+
+    let mut walls = Vec::new();
+    for _ in 0..r.len()? {
+        walls.push(Wall {
+            x1: r.i32()?,
+            y1: r.i32()?,
+            x2: r.i32()?,
+            y2: r.i32()?,
+        });
+    }
+
+    let mut monsters = Vec::new();
+    for _ in 0..r.len()? {
+        monsters.push(MonsterArea {
+            kind: r.string()?,
+            x1: r.f32()?,
+            y1: r.f32()?,
+            x2: r.f32()?,
+            y2: r.f32()?,
+        });
+    }
+
+    Ok(MapInfo {
+        min_x,
+        max_x,
+        min_y,
+        max_y,
+        spawns,
+        doors,
+        transporters,
+        npcs,
+        hash,
+        walls,
+        monsters,
+    })
+}
+
+fn read_world(bytes: &[u8]) -> Result<Vec<(String, Grid, MapInfo)>, String> {
+    let mut r = Reader { bytes, position: 0 };
+    if r.take(MAGIC.len())? != MAGIC {
+        return Err("not a world".to_string());
+    }
+    let version = r.u32()?;
+    if version != FORMAT_VERSION {
+        return Err(format!("unsupported world format version {}", version));
+    }
+
+    let mut maps = Vec::new();
+    for _ in 0..r.len()? {
+        let map_name = r.string()?;
+        let grid = read_grid(&mut r)?;
+        let info = read_info(&mut r)?;
+        if grid.min_x != info.min_x
+            || grid.min_y != info.min_y
+            || i64::from(grid.width) != i64::from(info.max_x) - i64::from(info.min_x)
+            || grid.data.len() as i64
+                != i64::from(grid.width) * (i64::from(info.max_y) - i64::from(info.min_y))
+        {
+            return Err(format!("{}'s grid doesn't match its bounds", map_name));
+        }
+        maps.push((map_name, grid, info));
+    }
+    Ok(maps)
+}
+
+/// Returns every prepared map as a world that can be loaded with `load_world`
+#[wasm_bindgen]
+pub fn export_world() -> Vec<u8> {
+    let grids = GRIDS.read().unwrap();
+    let maps = MAPS.read().unwrap();
+
+    let mut map_names: Vec<&String> = grids.keys().filter(|m| maps.contains_key(*m)).collect();
+    map_names.sort();
+
+    let mut w = Writer { bytes: Vec::new() };
+    w.bytes.extend_from_slice(MAGIC);
+    w.u32(FORMAT_VERSION);
+    w.len(map_names.len());
+    for map_name in map_names {
+        w.string(map_name);
+        write_grid(&mut w, &grids[map_name]);
+        write_info(&mut w, &maps[map_name]);
+    }
+    w.bytes
+}
+
+/// Loads the maps of a world made with `export_world`, instead of preparing them from 'G'. Throws
+/// `InvalidInput`, without loading any of its maps, if the world is malformed.
+#[wasm_bindgen]
+pub fn load_world(bytes: &[u8]) -> Result<(), QueryError> {
+    let world = read_world(bytes).map_err(QueryError::InvalidInput)?;
+
+    let mut loaded_grids = HashMap::new();
+    let mut loaded_maps = HashMap::new();
+    for (map_name, grid, info) in world {
+        loaded_grids.insert(map_name.to_string(), Arc::new(grid));
+        loaded_maps.insert(map_name, Arc::new(info));
+    }
+
+    GRIDS.write().unwrap().extend(loaded_grids);
+    MAPS.write().unwrap().extend(loaded_maps);
+    Ok(())
+}
+
+#[cfg(all(test, feature = "prepare"))]
+mod tests {
+    use super::super::testing::{random_map, Rng};
+    use super::super::WALKABLE;
+    use super::*;
+
+    fn header(maps: usize) -> Writer {
+        let mut w = Writer { bytes: Vec::new() };
+        w.bytes.extend_from_slice(MAGIC);
+        w.u32(FORMAT_VERSION);
+        w.len(maps);
+        w
+    }
+
+    // A world with only this map in it
+    fn world_of(map_name: &str) -> Vec<u8> {
+        let mut w = header(1);
+        w.string(map_name);
+        write_grid(&mut w, &GRIDS.read().unwrap()[map_name]);
+        write_info(&mut w, &MAPS.read().unwrap()[map_name]);
+        w.bytes
+    }
+
+    #[test]
+    fn exported_worlds_read_back_the_same() {
+        let mut rng = Rng::new(400);
+        let grid = random_map("test_world", &mut rng);
+
+        let bytes = export_world();
+        let world = read_world(&bytes).unwrap();
+        let (_, read_grid, read_info) = world
+            .iter()
+            .find(|(map_name, _, _)| map_name == "test_world")
+            .unwrap();
+        assert_eq!(read_grid.data, grid.data);
+        assert!(read_grid.tiles == grid.tiles);
+        assert_eq!(read_info.hash, MAPS.read().unwrap()["test_world"].hash);
+
+        // Writing what was read gives the same bytes
+        let mut w = header(world.len());
+        for (map_name, grid, info) in &world {
+            w.string(map_name);
+            write_grid(&mut w, grid);
+            write_info(&mut w, info);
+        }
+        assert_eq!(w.bytes, bytes);
+    }
+
+    #[test]
+    fn malformed_worlds_are_rejected() {
+        let mut rng = Rng::new(401);
+        random_map("test_world_malformed", &mut rng);
+        let bytes = world_of("test_world_malformed");
+        assert!(read_world(&bytes).is_ok());
+
+        for length in 0..bytes.len() {
+            assert!(read_world(&bytes[..length]).is_err(), "{} bytes", length);
+        }
+        assert!(read_world(b"not a world").is_err());
+
+        // A spawn without a y, and a grid that doesn't start where its map does
+        let rewrite = |change: &dyn Fn(&mut Grid, &mut MapInfo)| {
+            let mut w = header(1);
+            for (map_name, mut grid, mut info) in read_world(&bytes).unwrap() {
+                change(&mut grid, &mut info);
+                w.string(&map_name);
+                write_grid(&mut w, &grid);
+                write_info(&mut w, &info);
+            }
+            w.bytes
+        };
+        assert!(read_world(&rewrite(&|_, info| info.spawns[0].truncate(1))).is_err());
+        assert!(read_world(&rewrite(&|grid, _| grid.min_x += 1)).is_err());
+        assert!(read_world(&rewrite(&|grid, _| grid.min_y -= 1)).is_err());
+        assert!(read_world(&rewrite(&|_, _| {})).is_ok());
+
+        // A run much longer than the grid
+        let mut w = header(1);
+        w.string("test_world_long_run");
+        w.i32(10);
+        w.i32(0);
+        w.i32(0);
+        w.len(100);
+        w.len(1);
+        w.u8(WALKABLE);
+        w.u32(u32::MAX);
+        assert!(read_world(&w.bytes).is_err());
+
+        // A grid too big to be indexed, which shouldn't be allocated
+        let mut w = header(1);
+        w.string("test_world_huge");
+        w.i32(1);
+        w.i32(0);
+        w.i32(0);
+        w.len(u32::MAX as usize);
+        w.len(0);
+        assert!(read_world(&w.bytes).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+// `update` drops every prepared map that isn't in the new 'G', so it's tested in its own process
+// instead of next to the tests that prepare their own maps.
+#![cfg(feature = "prepare")]
+
+use alpathfinder::{get_map_hash, is_prepared, update_from_json};
+
+// A 'G' with the maps, each given as (name, max_x, walls)
+fn g(version: u64, maps: &[(&str, i32, &str)]) -> String {
+    let geometry: Vec<String> = maps
+        .iter()
+        .map(|(name, max_x, walls)| {
+            format!(
+                r#""{}":{{"min_x":0,"max_x":{},"min_y":0,"max_y":100,"x_lines":[{}]}}"#,
+                name, max_x, walls
+            )
+        })
+        .collect();
+    let info: Vec<String> = maps
+        .iter()
+        .map(|(name, _, _)| format!(r#""{}":{{"name":"{}","spawns":[[5,5]]}}"#, name, name))
+        .collect();
+    format!(
+        r#"{{"version":{},"geometry":{{{}}},"maps":{{{}}}}}"#,
+        version,
+        geometry.join(","),
+        info.join(",")
+    )
+}
+
+fn prepared(map_name: &str) -> bool {
+    is_prepared(Some(map_name.to_string()))
+}
+
+#[test]
+fn update_prepares_changed_maps_and_drops_the_rest() {
+    let first = g(1, &[("a", 100, ""), ("b", 100, ""), ("c", 100, "")]);
+    assert_eq!(update_from_json(&first, None).unwrap(), ["a", "b", "c"]);
+
+    // The same version is skipped, even if a map changed
+    let changed = g(
+        1,
+        &[("a", 100, ""), ("b", 100, "[50,0,100]"), ("c", 100, "")],
+    );
+    assert!(update_from_json(&changed, None).unwrap().is_empty());
+    let hash = get_map_hash("b").unwrap();
+
+    // A new version only prepares the map that changed
+    let changed = g(
+        2,
+        &[("a", 100, ""), ("b", 100, "[50,0,100]"), ("c", 100, "")],
+    );
+    assert_eq!(update_from_json(&changed, None).unwrap(), ["b"]);
+    assert_ne!(get_map_hash("b").unwrap(), hash);
+
+    // Removed maps, and maps that can't be prepared anymore, are dropped
+    let removed = g(3, &[("a", 100, ""), ("b", -100, "[50,0,100]")]);
+    assert!(update_from_json(&removed, None).unwrap().is_empty());
+    assert!(prepared("a"));
+    assert!(!prepared("b"));
+    assert!(!prepared("c"));
+}
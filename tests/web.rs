@@ -0,0 +1,141 @@
+// Tests of the exports from JavaScript's side of the wasm-bindgen boundary: the shapes of the
+// values JavaScript gets back, and the errors it catches. Run with `wasm-pack test --node`.
+#![cfg(all(target_arch = "wasm32", feature = "prepare"))]
+
+use alpathfinder::*;
+use js_sys::{Array, Reflect, JSON};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_test::*;
+
+// Two rooms, on either side of a wall at x = 0, with a door in the left one
+const G: &str = r#"{
+    "geometry": {"web": {"min_x": -100, "max_x": 100, "min_y": -100, "max_y": 100, "x_lines": [[0, -100, 100]]}},
+    "maps": {"web": {"name": "web", "spawns": [[-50, 0], [50, 0, 10, 20]], "doors": [[-50, 50, 10, 10, "web", 0], ["bad"]]}}
+}"#;
+
+fn g() -> JsValue {
+    JSON::parse(G).unwrap()
+}
+
+fn prepared() {
+    prepare(&g(), None).unwrap();
+}
+
+fn get(value: &JsValue, key: &str) -> JsValue {
+    Reflect::get(value, &key.into()).unwrap()
+}
+
+fn number(value: &JsValue, key: &str) -> f64 {
+    get(value, key).as_f64().unwrap()
+}
+
+// What JavaScript catches when the export throws
+fn thrown<T: std::fmt::Debug>(result: Result<T, QueryError>) -> JsValue {
+    result.unwrap_err().into()
+}
+
+#[wasm_bindgen_test]
+fn errors_are_named_after_what_went_wrong() {
+    prepared();
+
+    let error = thrown(find_path("web_missing", 0.0, 0.0, 1.0, 1.0, None));
+    assert!(error.is_instance_of::<js_sys::Error>());
+    assert_eq!(get(&error, "name"), "NotPrepared");
+    assert!(get(&error, "message")
+        .as_string()
+        .unwrap()
+        .contains("web_missing"));
+
+    let error = thrown(find_path("web", -50.0, 0.0, 1000.0, 0.0, None));
+    assert_eq!(get(&error, "name"), "OutOfBounds");
+
+    let error = thrown(find_path(
+        "web",
+        -50.0,
+        0.0,
+        -60.0,
+        0.0,
+        Some("fast".into()),
+    ));
+    assert_eq!(get(&error, "name"), "InvalidInput");
+    let error = thrown(prepare(&JsValue::from(5), None));
+    assert_eq!(get(&error, "name"), "InvalidInput");
+}
+
+#[wasm_bindgen_test]
+fn missing_values_are_undefined_or_null_in_arrays() {
+    prepared();
+
+    // No path between the rooms, and no third spawn
+    assert!(find_path("web", -50.0, 0.0, 50.0, 0.0, None)
+        .unwrap()
+        .is_undefined());
+    assert!(get_spawn("web", 2).unwrap().is_undefined());
+
+    // The door that couldn't be parsed keeps its place in the array
+    let doors: Array = get_doors("web").unwrap().into();
+    assert_eq!(doors.length(), 2);
+    assert!(doors.get(0).is_object());
+    assert!(doors.get(1).is_null());
+
+    let queries = JSON::parse(
+        r#"[{"map": "web", "x1": -50, "y1": 0, "x2": -60, "y2": 10}, {"map": "web", "x1": -50, "y1": 0, "x2": 50, "y2": 0}]"#,
+    )
+    .unwrap();
+    let paths: Array = find_paths(&queries).unwrap().into();
+    assert_eq!(paths.length(), 2);
+    assert!(Array::is_array(&paths.get(0)));
+    assert!(paths.get(1).is_null());
+}
+
+#[wasm_bindgen_test]
+fn values_have_their_typescript_shapes() {
+    prepared();
+
+    // PathStep[]
+    let path: Array = find_path("web", -50.0, 0.0, -60.0, 10.0, None)
+        .unwrap()
+        .into();
+    assert!(path.length() >= 2);
+    assert_eq!(number(&path.get(0), "x"), -50.0);
+    assert_eq!(number(&path.get(0), "cost"), 0.0);
+    let last = path.get(path.length() - 1);
+    assert_eq!((number(&last, "x"), number(&last, "y")), (-60.0, 10.0));
+    assert!(number(&last, "cost") > 0.0);
+
+    // MapBounds
+    let bounds = get_map_bounds("web").unwrap();
+    assert_eq!(number(&bounds, "min_x"), -100.0);
+    assert_eq!(number(&bounds, "max_y"), 100.0);
+    let spawns: Array = get(&bounds, "spawns").into();
+    assert!(Array::is_array(&spawns.get(0)));
+
+    // Spawn, where the sizes 'G' doesn't have are left out rather than null
+    let spawn = get_spawn("web", 0).unwrap();
+    assert_eq!((number(&spawn, "x"), number(&spawn, "y")), (-50.0, 0.0));
+    assert!(!Reflect::has(&spawn, &"width".into()).unwrap());
+    let spawn = get_spawn("web", 1).unwrap();
+    assert_eq!(
+        (number(&spawn, "width"), number(&spawn, "height")),
+        (10.0, 20.0)
+    );
+
+    // Door, without a `kind` if 'G' doesn't give it one
+    let door = Array::from(&get_doors("web").unwrap()).get(0);
+    assert_eq!(get(&door, "map"), "web");
+    assert_eq!(number(&door, "spawn"), 0.0);
+    assert!(!Reflect::has(&door, &"kind".into()).unwrap());
+
+    // { [map: string]: string } is a plain object, not a `Map`
+    let hashes = get_g_map_hashes(&g()).unwrap();
+    assert!(!hashes.is_instance_of::<js_sys::Map>());
+    assert_eq!(get(&hashes, "web"), get_map_hash("web").unwrap());
+
+    // WalkablePolygon[], with rings of [x, y] pairs
+    let polygons: Array = get_walkable_polygons("web").unwrap().into();
+    assert_eq!(polygons.length(), 2);
+    let exterior: Array = get(&polygons.get(0), "exterior").into();
+    let corner: Array = exterior.get(0).into();
+    assert_eq!(corner.length(), 2);
+    assert!(Array::is_array(&get(&polygons.get(0), "holes")));
+}